@@ -3,8 +3,10 @@ extern crate core;
 use crate::renderer::sprite::Sprite;
 use crate::renderer::{color, Layer, RenderContext, Renderer, TextureRef};
 use crate::renderer_ext::bitmap_font::{BitmapFont, BitmapFontSettings, TextAlignment};
+use crate::renderer_ext::capture::{CaptureSettings, FrameCapture};
 use crate::renderer_ext::context_ext::RenderContextExt;
 use crate::renderer_ext::sprite::{GridMode, SpriteExt};
+use crate::renderer_ext::sprite_sheet::SpriteSheet;
 use crate::GridMode::CellSize;
 use cgmath::Vector2;
 use rand::prelude::*;
@@ -26,6 +28,9 @@ const POINTS_SIZE: i32 = 12;
 const POINTS_MARGIN: i32 = 6;
 const TOLERANCE: i32 = BALL_VELOCITY;
 
+const SCREENSHOT_BUTTON: VirtualKeyCode = VirtualKeyCode::F12;
+const RECORDING_BUTTON: VirtualKeyCode = VirtualKeyCode::F11;
+
 const BACKGROUND_LAYER: Layer = Layer(0);
 const GAME_LAYER: Layer = Layer(1);
 const UI_LAYER: Layer = Layer(2);
@@ -41,16 +46,16 @@ struct Sprites {
 
 impl Sprites {
     fn load(renderer: &mut Renderer) -> Sprites {
-        let wall_sprite = &renderer.load_sprite("sprites/wall.png").unwrap();
-        let wall = wall_sprite.uniform_grid(CellSize((64, 64).into()));
+        let wall_sheet = SpriteSheet::load(renderer, "sprites/wall.png").unwrap();
+        let wall = wall_sheet.grid("wall").unwrap().to_vec();
 
-        let sprites_sprite = &renderer.load_sprite("sprites/sprites.png").unwrap();
-        let palette = sprites_sprite.slice((32, 128).into(), (0, 0).into());
-        let ball = sprites_sprite.slice((32, 32).into(), (32, 0).into());
-        let point = sprites_sprite.slice((16, 16).into(), (32, 32).into());
+        let sprites_sheet = SpriteSheet::load(renderer, "sprites/sprites.png").unwrap();
+        let palette = sprites_sheet.get("palette").unwrap().clone();
+        let ball = sprites_sheet.get("ball").unwrap().clone();
+        let point = sprites_sheet.get("point").unwrap().clone();
 
         let font_sprite = &renderer.load_sprite("sprites/font.png").unwrap();
-        let font_grid = font_sprite.uniform_grid(CellSize((16, 16).into()));
+        let font_grid = font_sprite.uniform_grid(CellSize((16, 16).into())).unwrap();
         let score_font = BitmapFont::new(&font_grid, '0'..='9', BitmapFontSettings::default());
 
         Sprites {
@@ -388,6 +393,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut renderer = Renderer::new(&window)?;
     let textures = Sprites::load(&mut renderer);
     let mut state = State::new();
+    let mut frame_capture = FrameCapture::new(CaptureSettings::default());
 
     let mut last_frame_finished = chrono::Utc::now();
     let step_duration = chrono::Duration::seconds(1) / STEPS_PER_SECOND;
@@ -400,6 +406,23 @@ fn main() -> Result<(), Box<dyn Error>> {
             WindowEvent::CloseRequested => {
                 *control_flow = ControlFlow::ExitWithCode(0);
             }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => match key {
+                SCREENSHOT_BUTTON => frame_capture.request_screenshot(),
+                RECORDING_BUTTON => {
+                    if let Err(e) = frame_capture.toggle_recording() {
+                        log::error!("Cannot start recording: {}", e);
+                    }
+                }
+                _ => (),
+            },
             _ => (),
         },
         Event::RedrawRequested(window_id) if window_id == window.id() => {
@@ -413,7 +436,13 @@ fn main() -> Result<(), Box<dyn Error>> {
                 state.render(ctx, &textures);
             });
             match render_result {
-                Ok(stats) => println!("{:?}", stats),
+                Ok(stats) => {
+                    println!("{:?}", stats);
+                    // only frames which were rendered are captured
+                    if let Err(e) = frame_capture.capture(&mut renderer) {
+                        log::error!("Frame capture failed: {}", e);
+                    }
+                }
                 Err(e) => match e {
                     _ => panic!("Unhandled render error: {}", e),
                 },
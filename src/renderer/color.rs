@@ -1,3 +1,9 @@
+use crate::renderer::Error;
+
+/// Color with sRGB-encoded channels, as in image editors and hex notation.
+///
+/// The renderer converts colors to linear space itself, so they look the same regardless
+/// of the surface format.
 #[derive(Debug, Default, Copy, Clone, PartialEq)]
 pub struct Color {
     pub r: f64,
@@ -6,6 +12,249 @@ pub struct Color {
     pub a: f64,
 }
 
+#[allow(dead_code)]
+impl Color {
+    pub const fn new(r: f64, g: f64, b: f64, a: f64) -> Color {
+        Color { r, g, b, a }
+    }
+
+    pub const fn rgb(r: f64, g: f64, b: f64) -> Color {
+        Color { r, g, b, a: 1.0 }
+    }
+
+    /// Parses `RGB`, `RGBA`, `RRGGBB` or `RRGGBBAA` hex notation, optionally prefixed with `#`.
+    pub fn from_hex(hex: &str) -> Result<Color, Error> {
+        let invalid = || Error::InvalidColor(hex.to_string());
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        // `from_str_radix` alone would also accept signs like `+f`
+        if !digits.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let parse = |digits: &str| u8::from_str_radix(digits, 16).map_err(|_| invalid());
+        let short = |id: usize| parse(&digits[id..=id]).map(|value| value * 17);
+        let long = |id: usize| parse(&digits[2 * id..2 * id + 2]);
+
+        let [r, g, b, a] = match digits.len() {
+            3 => [short(0)?, short(1)?, short(2)?, 255],
+            4 => [short(0)?, short(1)?, short(2)?, short(3)?],
+            6 => [long(0)?, long(1)?, long(2)?, 255],
+            8 => [long(0)?, long(1)?, long(2)?, long(3)?],
+            _ => return Err(invalid()),
+        };
+        Ok(Color::from_rgba8([r, g, b, a]))
+    }
+
+    /// Formats the color as `#RRGGBBAA`.
+    pub fn to_hex(self) -> String {
+        let [r, g, b, a] = self.to_rgba8();
+        format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    }
+
+    pub fn from_rgba8(rgba: [u8; 4]) -> Color {
+        let channel = |value: u8| value as f64 / 255.0;
+        Color::new(
+            channel(rgba[0]),
+            channel(rgba[1]),
+            channel(rgba[2]),
+            channel(rgba[3]),
+        )
+    }
+
+    pub fn to_rgba8(self) -> [u8; 4] {
+        let channel = |value: f64| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        [
+            channel(self.r),
+            channel(self.g),
+            channel(self.b),
+            channel(self.a),
+        ]
+    }
+
+    /// Creates a color from hue in degrees, and saturation and value in `0.0..=1.0`.
+    pub fn from_hsv(hue: f64, saturation: f64, value: f64, a: f64) -> Color {
+        let chroma = value * saturation;
+        Color::from_hue_and_chroma(hue, chroma, value - chroma, a)
+    }
+
+    /// Returns hue in degrees, saturation and value.
+    pub fn to_hsv(self) -> (f64, f64, f64) {
+        let (max, min) = self.max_min();
+        let chroma = max - min;
+        let saturation = if max == 0.0 { 0.0 } else { chroma / max };
+        (self.hue(), saturation, max)
+    }
+
+    /// Creates a color from hue in degrees, and saturation and lightness in `0.0..=1.0`.
+    pub fn from_hsl(hue: f64, saturation: f64, lightness: f64, a: f64) -> Color {
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        Color::from_hue_and_chroma(hue, chroma, lightness - chroma / 2.0, a)
+    }
+
+    /// Returns hue in degrees, saturation and lightness.
+    pub fn to_hsl(self) -> (f64, f64, f64) {
+        let (max, min) = self.max_min();
+        let chroma = max - min;
+        let lightness = (max + min) / 2.0;
+        let saturation = if lightness == 0.0 || lightness == 1.0 {
+            0.0
+        } else {
+            chroma / (1.0 - (2.0 * lightness - 1.0).abs())
+        };
+        (self.hue(), saturation, lightness)
+    }
+
+    /// Linear interpolation between the colors, with `t` of 0 giving `self` and 1 - `other`.
+    pub fn lerp(self, other: Color, t: f64) -> Color {
+        let mix = |from: f64, to: f64| from + (to - from) * t;
+        Color::new(
+            mix(self.r, other.r),
+            mix(self.g, other.g),
+            mix(self.b, other.b),
+            mix(self.a, other.a),
+        )
+    }
+
+    pub fn premultiplied(self) -> Color {
+        Color::new(self.r * self.a, self.g * self.a, self.b * self.a, self.a)
+    }
+
+    /// Moves HSL lightness by `amount`; negative amounts darken the color.
+    pub fn adjust_brightness(self, amount: f64) -> Color {
+        let (hue, saturation, lightness) = self.to_hsl();
+        Color::from_hsl(
+            hue,
+            saturation,
+            (lightness + amount).clamp(0.0, 1.0),
+            self.a,
+        )
+    }
+
+    /// Moves HSL saturation by `amount`; negative amounts desaturate the color.
+    pub fn adjust_saturation(self, amount: f64) -> Color {
+        let (hue, saturation, lightness) = self.to_hsl();
+        Color::from_hsl(
+            hue,
+            (saturation + amount).clamp(0.0, 1.0),
+            lightness,
+            self.a,
+        )
+    }
+
+    pub fn with_alpha(self, a: f64) -> Color {
+        Color { a, ..self }
+    }
+
+    /// Decodes the color channels into linear space; alpha is linear already.
+    pub fn to_linear(self) -> Color {
+        let decode = |channel: f64| {
+            if channel <= 0.04045 {
+                channel / 12.92
+            } else {
+                ((channel + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        Color::new(decode(self.r), decode(self.g), decode(self.b), self.a)
+    }
+
+    /// Encodes linear color channels into sRGB space.
+    pub fn from_linear(linear: Color) -> Color {
+        let encode = |channel: f64| {
+            if channel <= 0.0031308 {
+                channel * 12.92
+            } else {
+                1.055 * channel.powf(1.0 / 2.4) - 0.055
+            }
+        };
+        Color::new(
+            encode(linear.r),
+            encode(linear.g),
+            encode(linear.b),
+            linear.a,
+        )
+    }
+
+    fn from_hue_and_chroma(hue: f64, chroma: f64, min: f64, a: f64) -> Color {
+        let sector = hue.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+        let (r, g, b) = match sector as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        Color::new(r + min, g + min, b + min, a)
+    }
+
+    fn max_min(self) -> (f64, f64) {
+        (
+            self.r.max(self.g).max(self.b),
+            self.r.min(self.g).min(self.b),
+        )
+    }
+
+    fn hue(self) -> f64 {
+        let (max, min) = self.max_min();
+        let chroma = max - min;
+        let hue = if chroma == 0.0 {
+            0.0
+        } else if max == self.r {
+            ((self.g - self.b) / chroma).rem_euclid(6.0)
+        } else if max == self.g {
+            (self.b - self.r) / chroma + 2.0
+        } else {
+            (self.r - self.g) / chroma + 4.0
+        };
+        hue * 60.0
+    }
+}
+
+/// Colors of a sprite's corners, interpolated across it when drawing.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct CornerColors {
+    pub bottom_left: Color,
+    pub bottom_right: Color,
+    pub top_right: Color,
+    pub top_left: Color,
+}
+
+#[allow(dead_code)]
+impl CornerColors {
+    pub fn uniform(color: Color) -> CornerColors {
+        CornerColors {
+            bottom_left: color,
+            bottom_right: color,
+            top_right: color,
+            top_left: color,
+        }
+    }
+
+    pub fn vertical(bottom: Color, top: Color) -> CornerColors {
+        CornerColors {
+            bottom_left: bottom,
+            bottom_right: bottom,
+            top_right: top,
+            top_left: top,
+        }
+    }
+
+    pub fn horizontal(left: Color, right: Color) -> CornerColors {
+        CornerColors {
+            bottom_left: left,
+            bottom_right: right,
+            top_right: right,
+            top_left: left,
+        }
+    }
+}
+
+impl From<Color> for CornerColors {
+    fn from(color: Color) -> Self {
+        CornerColors::uniform(color)
+    }
+}
+
 impl From<wgpu::Color> for Color {
     fn from(color: wgpu::Color) -> Self {
         Color {
@@ -70,3 +319,240 @@ pub const YELLOW: Color = Color {
     b: 0.0,
     a: 1.0,
 };
+#[allow(dead_code)]
+pub const TRANSPARENT: Color = Color {
+    r: 0.0,
+    g: 0.0,
+    b: 0.0,
+    a: 0.0,
+};
+#[allow(dead_code)]
+pub const CYAN: Color = Color {
+    r: 0.0,
+    g: 1.0,
+    b: 1.0,
+    a: 1.0,
+};
+#[allow(dead_code)]
+pub const MAGENTA: Color = Color {
+    r: 1.0,
+    g: 0.0,
+    b: 1.0,
+    a: 1.0,
+};
+#[allow(dead_code)]
+pub const ORANGE: Color = Color {
+    r: 1.0,
+    g: 0.5,
+    b: 0.0,
+    a: 1.0,
+};
+#[allow(dead_code)]
+pub const PURPLE: Color = Color {
+    r: 0.5,
+    g: 0.0,
+    b: 0.5,
+    a: 1.0,
+};
+#[allow(dead_code)]
+pub const PINK: Color = Color {
+    r: 1.0,
+    g: 0.75,
+    b: 0.8,
+    a: 1.0,
+};
+#[allow(dead_code)]
+pub const BROWN: Color = Color {
+    r: 0.6,
+    g: 0.4,
+    b: 0.2,
+    a: 1.0,
+};
+#[allow(dead_code)]
+pub const GRAY: Color = Color {
+    r: 0.5,
+    g: 0.5,
+    b: 0.5,
+    a: 1.0,
+};
+#[allow(dead_code)]
+pub const LIGHT_GRAY: Color = Color {
+    r: 0.75,
+    g: 0.75,
+    b: 0.75,
+    a: 1.0,
+};
+#[allow(dead_code)]
+pub const DARK_GRAY: Color = Color {
+    r: 0.25,
+    g: 0.25,
+    b: 0.25,
+    a: 1.0,
+};
+#[allow(dead_code)]
+pub const NAVY: Color = Color {
+    r: 0.0,
+    g: 0.0,
+    b: 0.5,
+    a: 1.0,
+};
+#[allow(dead_code)]
+pub const TEAL: Color = Color {
+    r: 0.0,
+    g: 0.5,
+    b: 0.5,
+    a: 1.0,
+};
+#[allow(dead_code)]
+pub const OLIVE: Color = Color {
+    r: 0.5,
+    g: 0.5,
+    b: 0.0,
+    a: 1.0,
+};
+#[allow(dead_code)]
+pub const MAROON: Color = Color {
+    r: 0.5,
+    g: 0.0,
+    b: 0.0,
+    a: 1.0,
+};
+#[allow(dead_code)]
+pub const LIME: Color = Color {
+    r: 0.75,
+    g: 1.0,
+    b: 0.0,
+    a: 1.0,
+};
+#[allow(dead_code)]
+pub const SKY_BLUE: Color = Color {
+    r: 0.53,
+    g: 0.81,
+    b: 0.92,
+    a: 1.0,
+};
+#[allow(dead_code)]
+pub const GOLD: Color = Color {
+    r: 1.0,
+    g: 0.84,
+    b: 0.0,
+    a: 1.0,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Color, expected: Color) {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-6;
+        assert!(
+            close(actual.r, expected.r)
+                && close(actual.g, expected.g)
+                && close(actual.b, expected.b)
+                && close(actual.a, expected.a),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn parses_hex_notations() {
+        assert_eq!(Color::from_hex("#ff0000").unwrap(), RED);
+        assert_eq!(Color::from_hex("0f0").unwrap(), GREEN);
+        assert_eq!(Color::from_hex("#0000ffff").unwrap(), BLUE);
+        assert_eq!(Color::from_hex("#fff0").unwrap(), WHITE.with_alpha(0.0));
+    }
+
+    #[test]
+    fn rejects_invalid_hex() {
+        assert!(Color::from_hex("#ff000").is_err());
+        assert!(Color::from_hex("#gg0000").is_err());
+        assert!(Color::from_hex("").is_err());
+        assert!(Color::from_hex("#ąę").is_err());
+        assert!(Color::from_hex("#+f+f+f").is_err());
+        assert!(Color::from_hex("+fff").is_err());
+    }
+
+    #[test]
+    fn formats_hex() {
+        assert_eq!(YELLOW.to_hex(), "#ffff00ff");
+        assert_eq!(Color::from_hex("#12345678").unwrap().to_hex(), "#12345678");
+    }
+
+    #[test]
+    fn converts_rgba8() {
+        assert_eq!(Color::from_rgba8([255, 0, 255, 0]), MAGENTA.with_alpha(0.0));
+        assert_eq!(
+            Color::new(2.0, -1.0, 0.5, 1.0).to_rgba8(),
+            [255, 0, 128, 255]
+        );
+    }
+
+    #[test]
+    fn converts_hsv() {
+        assert_close(Color::from_hsv(0.0, 1.0, 1.0, 1.0), RED);
+        assert_close(Color::from_hsv(120.0, 1.0, 1.0, 1.0), GREEN);
+        assert_close(
+            Color::from_hsv(240.0, 1.0, 0.5, 1.0),
+            Color::rgb(0.0, 0.0, 0.5),
+        );
+        assert_close(Color::from_hsv(-120.0, 1.0, 1.0, 1.0), BLUE);
+
+        let (hue, saturation, value) = ORANGE.to_hsv();
+        assert_close(Color::from_hsv(hue, saturation, value, 1.0), ORANGE);
+        assert_eq!(GRAY.to_hsv(), (0.0, 0.0, 0.5));
+    }
+
+    #[test]
+    fn converts_hsl() {
+        assert_close(Color::from_hsl(60.0, 1.0, 0.5, 1.0), YELLOW);
+        assert_close(Color::from_hsl(0.0, 0.0, 0.25, 1.0), DARK_GRAY);
+
+        let (hue, saturation, lightness) = PINK.to_hsl();
+        assert_close(Color::from_hsl(hue, saturation, lightness, 1.0), PINK);
+        assert_eq!(WHITE.to_hsl(), (0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn converts_between_srgb_and_linear() {
+        assert_close(WHITE.to_linear(), WHITE);
+        assert_close(BLACK.to_linear(), BLACK);
+        assert_close(
+            Color::new(0.5, 0.5, 0.5, 0.5).to_linear(),
+            Color::new(0.214041, 0.214041, 0.214041, 0.5),
+        );
+        assert_close(Color::from_linear(ORANGE.to_linear()), ORANGE);
+        assert_close(
+            Color::from_linear(Color::rgb(0.001, 0.2, 0.9)).to_linear(),
+            Color::rgb(0.001, 0.2, 0.9),
+        );
+    }
+
+    #[test]
+    fn interpolates() {
+        assert_close(BLACK.lerp(WHITE, 0.5), GRAY);
+        assert_close(RED.lerp(BLUE, 0.0), RED);
+        assert_close(RED.lerp(BLUE, 1.0), BLUE);
+    }
+
+    #[test]
+    fn premultiplies_alpha() {
+        assert_close(
+            Color::new(1.0, 0.5, 0.2, 0.5).premultiplied(),
+            Color::new(0.5, 0.25, 0.1, 0.5),
+        );
+    }
+
+    #[test]
+    fn adjusts_brightness_and_saturation() {
+        assert_close(GRAY.adjust_brightness(0.25), LIGHT_GRAY);
+        assert_close(RED.adjust_brightness(-0.25), MAROON);
+        assert_close(WHITE.adjust_brightness(1.0), WHITE);
+        assert_close(RED.adjust_saturation(-1.0), GRAY);
+        assert_close(
+            Color::from_hsl(200.0, 0.2, 0.4, 0.3).adjust_saturation(0.5),
+            Color::from_hsl(200.0, 0.7, 0.4, 0.3),
+        );
+    }
+}
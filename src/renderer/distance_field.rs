@@ -0,0 +1,70 @@
+use crate::renderer::color::Color;
+use cgmath::Vector2;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum DistanceFieldKind {
+    /// Distance stored in the red channel.
+    #[default]
+    SingleChannel,
+    /// Distance is the median of the red, green and blue channels, which keeps sharp corners.
+    MultiChannel,
+}
+
+/// Describes how a texture encodes distances to shape edges.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DistanceField {
+    pub kind: DistanceFieldKind,
+    /// Distance in texels between the values 0 and 1, as set when generating the field.
+    pub range: f32,
+}
+
+/// Effects of sprites drawn with the distance field shader.
+///
+/// Widths and offsets are in texels of the field, so they scale together with the sprite.
+/// Effects reaching outside the shape have to fit in the empty space around it.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DistanceFieldEffects {
+    pub outline_width: f32,
+    pub outline_color: Color,
+    pub shadow_offset: Vector2<f32>,
+    /// Distance over which the shadow fades out.
+    pub shadow_softness: f32,
+    pub shadow_color: Color,
+}
+
+impl Default for DistanceFieldEffects {
+    fn default() -> Self {
+        DistanceFieldEffects {
+            outline_width: 0.0,
+            outline_color: Color::default(),
+            shadow_offset: (0.0, 0.0).into(),
+            shadow_softness: 0.0,
+            shadow_color: Color::default(),
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl DistanceFieldEffects {
+    pub fn with_outline(self, width: f32, color: Color) -> DistanceFieldEffects {
+        DistanceFieldEffects {
+            outline_width: width,
+            outline_color: color,
+            ..self
+        }
+    }
+
+    pub fn with_shadow(
+        self,
+        offset: Vector2<f32>,
+        softness: f32,
+        color: Color,
+    ) -> DistanceFieldEffects {
+        DistanceFieldEffects {
+            shadow_offset: offset,
+            shadow_softness: softness,
+            shadow_color: color,
+            ..self
+        }
+    }
+}
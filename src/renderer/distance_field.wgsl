@@ -0,0 +1,147 @@
+struct CameraUniform {
+    view_proj: mat4x4<f32>
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    @location(0) pos: vec2<f32>,
+    @location(1) tex: vec2<f32>
+}
+
+struct InstanceInput {
+    @location(2) matrix_1: vec3<f32>,
+    @location(3) matrix_2: vec3<f32>,
+    @location(4) matrix_3: vec3<f32>,
+    @location(5) tex_lower_bounds: vec2<f32>,
+    @location(6) tex_higher_bounds: vec2<f32>,
+    @location(7) color: vec4<f32>,
+    @location(8) outline_color: vec4<f32>,
+    @location(9) shadow_color: vec4<f32>,
+    @location(10) shadow_offset: vec2<f32>,
+    // field range, scale, outline width, shadow softness
+    @location(11) params: vec4<f32>,
+    @location(12) kind: f32
+}
+
+struct VertexOutput {
+    @builtin(position) clip_pos: vec4<f32>,
+    @location(0) tex: vec2<f32>,
+    @location(1) @interpolate(flat) color: vec4<f32>,
+    @location(2) @interpolate(flat) outline_color: vec4<f32>,
+    @location(3) @interpolate(flat) shadow_color: vec4<f32>,
+    @location(4) @interpolate(flat) shadow_offset: vec2<f32>,
+    @location(5) @interpolate(flat) params: vec4<f32>,
+    @location(6) @interpolate(flat) kind: f32
+}
+
+fn real_tex_coords(vertex: VertexInput, instance: InstanceInput) -> vec2<f32> {
+    var real_tex = vec2<f32>(0.0, 0.0);
+
+    if (vertex.tex.x < 0.5) {
+        real_tex.x = instance.tex_lower_bounds.x;
+    } else {
+        real_tex.x = instance.tex_higher_bounds.x;
+    }
+
+    if (vertex.tex.y < 0.5) {
+        real_tex.y = instance.tex_lower_bounds.y;
+    } else {
+        real_tex.y = instance.tex_higher_bounds.y;
+    }
+    return real_tex;
+}
+
+@vertex
+fn vertex(
+    model: VertexInput,
+    instance: InstanceInput
+) -> VertexOutput {
+    var out: VertexOutput;
+    var instance_matrix = mat3x3<f32>(instance.matrix_1, instance.matrix_2, instance.matrix_3);
+
+    var pos = vec3<f32>(model.pos.xy, 1.0);
+    var transformed_pos = instance_matrix * pos;
+    out.clip_pos = vec4<f32>(transformed_pos.xy, 0.0, transformed_pos.z);
+    out.clip_pos = camera.view_proj * out.clip_pos;
+
+    out.tex = real_tex_coords(model, instance);
+    out.color = instance.color;
+    out.outline_color = instance.outline_color;
+    out.shadow_color = instance.shadow_color;
+    out.shadow_offset = instance.shadow_offset;
+    out.params = instance.params;
+    out.kind = instance.kind;
+
+    return out;
+}
+
+@group(1) @binding(0)
+var texture: texture_2d<f32>;
+@group(1) @binding(1)
+var sampler_: sampler;
+
+fn median(r: f32, g: f32, b: f32) -> f32 {
+    return max(min(r, g), min(max(r, g), b));
+}
+
+// signed distance to the shape's edge in texels, positive inside
+fn edge_distance(in: VertexOutput, tex: vec2<f32>) -> f32 {
+    let sampled = textureSampleLevel(texture, sampler_, tex, 0.0);
+    var value = sampled.r;
+    if (in.kind > 0.5) {
+        value = median(sampled.r, sampled.g, sampled.b);
+    }
+    return (value - 0.5) * in.params.x;
+}
+
+// coverage of a pixel by the area within `edge_distance` texels from the edge
+fn coverage(edge_distance: f32, scale: f32) -> f32 {
+    return clamp(edge_distance * scale + 0.5, 0.0, 1.0);
+}
+
+fn shade(in: VertexOutput) -> vec4<f32> {
+    let scale = in.params.y;
+    let outline_width = in.params.z;
+    let shadow_softness = in.params.w;
+
+    let shape_distance = edge_distance(in, in.tex);
+    let shadow_distance = edge_distance(in, in.tex - in.shadow_offset);
+    let fill_alpha = in.color.a * coverage(shape_distance, scale);
+    let outline_alpha = in.outline_color.a * coverage(shape_distance + outline_width, scale);
+    let shadow_alpha = in.shadow_color.a
+        * clamp(shadow_distance / max(shadow_softness, 1.0 / scale) + 0.5, 0.0, 1.0);
+
+    // fill over outline over shadow, blended with premultiplied colors
+    var alpha = fill_alpha;
+    var rgb = in.color.rgb * fill_alpha;
+    rgb = rgb + in.outline_color.rgb * outline_alpha * (1.0 - alpha);
+    alpha = alpha + outline_alpha * (1.0 - alpha);
+    rgb = rgb + in.shadow_color.rgb * shadow_alpha * (1.0 - alpha);
+    alpha = alpha + shadow_alpha * (1.0 - alpha);
+
+    if (alpha <= 0.0) {
+        return vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    }
+    return vec4<f32>(rgb / alpha, alpha);
+}
+
+fn srgb_encode(linear: f32) -> f32 {
+    if (linear <= 0.0031308) {
+        return linear * 12.92;
+    }
+    return 1.055 * pow(linear, 1.0 / 2.4) - 0.055;
+}
+
+// for sRGB surfaces, which encode linear colors on write
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+// for other surfaces, which store written values as they are
+@fragment
+fn fragment_srgb_encode(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = shade(in);
+    return vec4<f32>(srgb_encode(color.r), srgb_encode(color.g), srgb_encode(color.b), color.a);
+}
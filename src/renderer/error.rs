@@ -1,3 +1,4 @@
+use cgmath::Vector2;
 use std::fmt::Formatter;
 use std::{error, fmt, io};
 
@@ -8,6 +9,35 @@ pub enum Error {
     SurfaceError(wgpu::SurfaceError),
     IOError(io::Error),
     TextureEncodingError,
+    TextureLoadingInterrupted,
+    PixelsOutOfBounds,
+    UnsupportedTextureFormat,
+    TextureReadbackFailed,
+    NoFrameCaptured,
+    ImageSaveFailed(image::ImageError),
+    InvalidSpriteSheet {
+        line: usize,
+        reason: String,
+    },
+    MissingSprite(String),
+    InvalidAtlas(String),
+    GridMismatch {
+        sprite_size: Vector2<u32>,
+        margin: Vector2<u32>,
+        spacing: Vector2<u32>,
+    },
+    EmptyGrid,
+    SliceOutOfBounds {
+        sprite_size: Vector2<u32>,
+        slice_size: Vector2<u32>,
+        slice_offset: Vector2<u32>,
+    },
+    MissingAnimation(String),
+    EmptyAnimation(String),
+    MissingPixelData,
+    InvalidColor(String),
+    InvalidFont(String),
+    InvalidMarkup(String),
 }
 
 impl fmt::Display for Error {
@@ -19,6 +49,42 @@ impl fmt::Display for Error {
             NoDevice => write!(f, "Device not found"),
             IOError(err) => write!(f, "IO error: {}", err),
             TextureEncodingError => write!(f, "Cannot encode texture"),
+            TextureLoadingInterrupted => write!(f, "Texture loading was interrupted"),
+            PixelsOutOfBounds => write!(f, "Written pixels do not fit inside the sprite"),
+            UnsupportedTextureFormat => write!(f, "Texture format is not supported"),
+            TextureReadbackFailed => write!(f, "Cannot read texture back from the GPU"),
+            NoFrameCaptured => write!(f, "No frame has been captured yet"),
+            ImageSaveFailed(err) => write!(f, "Cannot save image: {}", err),
+            InvalidSpriteSheet { line, reason } => {
+                write!(f, "Invalid sprite sheet, line {}: {}", line, reason)
+            }
+            MissingSprite(name) => write!(f, "Sprite {} not found", name),
+            InvalidAtlas(reason) => write!(f, "Invalid atlas: {}", reason),
+            GridMismatch {
+                sprite_size,
+                margin,
+                spacing,
+            } => write!(
+                f,
+                "Sprite of size {}x{} cannot be evenly divided into cells with margin {}x{} and spacing {}x{}",
+                sprite_size.x, sprite_size.y, margin.x, margin.y, spacing.x, spacing.y
+            ),
+            EmptyGrid => write!(f, "Grid has no cells"),
+            SliceOutOfBounds {
+                sprite_size,
+                slice_size,
+                slice_offset,
+            } => write!(
+                f,
+                "Slice of size {}x{} at {}x{} does not fit inside sprite of size {}x{}",
+                slice_size.x, slice_size.y, slice_offset.x, slice_offset.y, sprite_size.x, sprite_size.y
+            ),
+            MissingAnimation(name) => write!(f, "Animation {} not found", name),
+            EmptyAnimation(name) => write!(f, "Animation {} has no frames", name),
+            MissingPixelData => write!(f, "Texture does not keep its pixels on the CPU"),
+            InvalidColor(color) => write!(f, "Invalid color {}", color),
+            InvalidFont(reason) => write!(f, "Invalid font: {}", reason),
+            InvalidMarkup(reason) => write!(f, "Invalid markup: {}", reason),
         }
     }
 }
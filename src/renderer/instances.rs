@@ -1,4 +1,5 @@
-use crate::renderer::color::Color;
+use crate::renderer::color::{Color, CornerColors};
+use crate::renderer::distance_field::{DistanceField, DistanceFieldEffects, DistanceFieldKind};
 use crate::renderer::pass::PassDescriptor;
 use crate::renderer::Layer;
 use cgmath::{Matrix3, Vector2};
@@ -12,41 +13,113 @@ pub(in crate::renderer) struct Instance {
     pub(in crate::renderer) tex_size: Vector2<u32>,
     pub(in crate::renderer) sprite_size: Vector2<u32>,
     pub(in crate::renderer) sprite_offset: Vector2<u32>,
-    pub(in crate::renderer) color: Color,
+    pub(in crate::renderer) uv_inset: f32,
+    pub(in crate::renderer) scale: f32,
+    pub(in crate::renderer) colors: CornerColors,
+    /// Id of the palette texture and the palette's row in it.
+    pub(in crate::renderer) palette: Option<(usize, u32)>,
+    pub(in crate::renderer) distance_field: Option<(DistanceField, DistanceFieldEffects)>,
+}
+
+// shader works in linear space, textures are decoded by sampling
+fn linear(color: Color) -> [f32; 4] {
+    let linear_color = color.to_linear();
+    [
+        linear_color.r as f32,
+        linear_color.g as f32,
+        linear_color.b as f32,
+        linear_color.a as f32,
+    ]
 }
 
 impl Instance {
     pub(in crate::renderer) fn to_raw(&self) -> InstanceRaw {
+        let (matrix, tex_lower_bounds, tex_higher_bounds) = self.placement();
+        InstanceRaw {
+            matrix,
+            tex_lower_bounds,
+            tex_higher_bounds,
+            colors: [
+                linear(self.colors.bottom_left),
+                linear(self.colors.bottom_right),
+                linear(self.colors.top_right),
+                linear(self.colors.top_left),
+            ],
+            palette: match self.palette {
+                Some((_, row)) => [1.0, row as f32],
+                None => [0.0, 0.0],
+            },
+        }
+    }
+
+    pub(in crate::renderer) fn to_distance_field_raw(&self) -> DistanceFieldInstanceRaw {
+        let (matrix, tex_lower_bounds, tex_higher_bounds) = self.placement();
+        let (field, effects) = self.distance_field.unwrap_or((
+            DistanceField {
+                kind: DistanceFieldKind::SingleChannel,
+                range: 1.0,
+            },
+            DistanceFieldEffects::default(),
+        ));
+        DistanceFieldInstanceRaw {
+            matrix,
+            tex_lower_bounds,
+            tex_higher_bounds,
+            color: linear(self.colors.bottom_left),
+            outline_color: linear(effects.outline_color),
+            shadow_color: linear(effects.shadow_color),
+            // texture rows go down, while world coordinates go up
+            shadow_offset: [
+                effects.shadow_offset.x / self.tex_size.x as f32,
+                -effects.shadow_offset.y / self.tex_size.y as f32,
+            ],
+            params: [
+                field.range,
+                self.scale,
+                effects.outline_width,
+                effects.shadow_softness,
+            ],
+            kind: match field.kind {
+                DistanceFieldKind::SingleChannel => 0.0,
+                DistanceFieldKind::MultiChannel => 1.0,
+            },
+        }
+    }
+
+    /// Transformation of the quad and its texture bounds.
+    fn placement(&self) -> ([[f32; 3]; 3], [f32; 2], [f32; 2]) {
         let tex_size: Vector2<f32> = (self.tex_size.x as f32, self.tex_size.y as f32).into();
         let sprite_offset: Vector2<f32> =
             (self.sprite_offset.x as f32, self.sprite_offset.y as f32).into();
         let sprite_size: Vector2<f32> =
             (self.sprite_size.x as f32, self.sprite_size.y as f32).into();
-        let sprite_higher_bounds = sprite_offset + sprite_size;
+        let inset: Vector2<f32> = (self.uv_inset, self.uv_inset).into();
+        let sprite_lower_bounds = sprite_offset + inset;
+        let sprite_higher_bounds = sprite_offset + sprite_size - inset;
 
         let translation = Matrix3::from_translation(self.position);
-        let scale = Matrix3::from_nonuniform_scale(sprite_size.x, sprite_size.y);
+        let scale =
+            Matrix3::from_nonuniform_scale(sprite_size.x * self.scale, sprite_size.y * self.scale);
         let matrix = translation * scale;
 
-        InstanceRaw {
-            matrix: matrix.into(),
-            tex_lower_bounds: [sprite_offset.x / tex_size.x, sprite_offset.y / tex_size.y],
-            tex_higher_bounds: [
+        (
+            matrix.into(),
+            [
+                sprite_lower_bounds.x / tex_size.x,
+                sprite_lower_bounds.y / tex_size.y,
+            ],
+            [
                 sprite_higher_bounds.x / tex_size.x,
                 sprite_higher_bounds.y / tex_size.y,
             ],
-            color: [
-                self.color.r as f32,
-                self.color.g as f32,
-                self.color.b as f32,
-                self.color.a as f32,
-            ],
-        }
+        )
     }
     pub(in crate::renderer) fn to_pass_descriptor(&self) -> PassDescriptor {
         PassDescriptor {
             texture_id: self.texture_id,
             layer: self.layer,
+            palette_texture_id: self.palette.map(|(id, _)| id),
+            distance_field: self.distance_field.is_some(),
         }
     }
 }
@@ -54,8 +127,24 @@ impl Instance {
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub(in crate::renderer) struct InstanceRaw {
+    matrix: [[f32; 3]; 3],
+    tex_lower_bounds: [f32; 2],
+    tex_higher_bounds: [f32; 2],
+    colors: [[f32; 4]; 4],
+    palette: [f32; 2],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(in crate::renderer) struct DistanceFieldInstanceRaw {
     matrix: [[f32; 3]; 3],
     tex_lower_bounds: [f32; 2],
     tex_higher_bounds: [f32; 2],
     color: [f32; 4],
+    outline_color: [f32; 4],
+    shadow_color: [f32; 4],
+    shadow_offset: [f32; 2],
+    /// Field range, scale, outline width and shadow softness.
+    params: [f32; 4],
+    kind: f32,
 }
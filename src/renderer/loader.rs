@@ -0,0 +1,120 @@
+use crate::renderer::sprite::Sprite;
+use crate::renderer::{Error, Renderer};
+use image::io::Reader as ImageReader;
+use image::DynamicImage;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+type DecodeResult = (usize, Result<DynamicImage, Error>);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LoadingProgress {
+    pub loaded: usize,
+    pub total: usize,
+}
+
+impl LoadingProgress {
+    #[allow(dead_code)]
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.loaded as f64 / self.total as f64
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn is_finished(&self) -> bool {
+        self.loaded == self.total
+    }
+}
+
+/// Loads sprites in the background: images are decoded on worker threads, while
+/// uploading them to the GPU happens on the render thread in `Renderer::upload_loaded`.
+#[derive(Debug)]
+pub struct SpriteLoader {
+    receiver: Receiver<DecodeResult>,
+    sprites: Vec<Option<Result<Sprite, Error>>>,
+    loaded: usize,
+}
+
+impl SpriteLoader {
+    pub(in crate::renderer) fn spawn(file_paths: &[&str]) -> SpriteLoader {
+        let paths: Arc<Vec<String>> = Arc::new(file_paths.iter().map(|p| p.to_string()).collect());
+        let next_path = Arc::new(AtomicUsize::new(0));
+        let (sender, receiver) = mpsc::channel();
+
+        let workers_count = thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(paths.len());
+        for _ in 0..workers_count {
+            let paths = paths.clone();
+            let next_path = next_path.clone();
+            let sender = sender.clone();
+            thread::spawn(move || loop {
+                let id = next_path.fetch_add(1, Ordering::SeqCst);
+                if id >= paths.len() {
+                    break;
+                }
+                let result = decode_image(&paths[id]);
+                if sender.send((id, result)).is_err() {
+                    break;
+                }
+            });
+        }
+
+        SpriteLoader {
+            receiver,
+            sprites: (0..paths.len()).map(|_| None).collect(),
+            loaded: 0,
+        }
+    }
+
+    pub fn progress(&self) -> LoadingProgress {
+        LoadingProgress {
+            loaded: self.loaded,
+            total: self.sprites.len(),
+        }
+    }
+
+    /// Returns loaded sprites in the order their paths were given, or the first error.
+    /// Must be called after loading has finished.
+    #[allow(dead_code)]
+    pub fn finish(self) -> Result<Vec<Sprite>, Error> {
+        self.sprites
+            .into_iter()
+            .map(|sprite| sprite.unwrap_or(Err(Error::TextureLoadingInterrupted)))
+            .collect()
+    }
+
+    pub(in crate::renderer) fn upload_pending(&mut self, renderer: &mut Renderer) {
+        loop {
+            match self.receiver.try_recv() {
+                Ok((id, result)) => {
+                    let sprite = result.map(|image| {
+                        let texture = renderer.create_texture(image);
+                        Sprite::from_whole_texture(&texture)
+                    });
+                    self.sprites[id] = Some(sprite);
+                    self.loaded += 1;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    // workers are gone, so whatever is still missing will never arrive
+                    for sprite in self.sprites.iter_mut().filter(|s| s.is_none()) {
+                        *sprite = Some(Err(Error::TextureLoadingInterrupted));
+                        self.loaded += 1;
+                    }
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn decode_image(file_path: &str) -> Result<DynamicImage, Error> {
+    let image = ImageReader::open(file_path)?;
+    image.decode().map_err(|_| Error::TextureEncodingError)
+}
@@ -1,14 +1,20 @@
 mod camera;
 pub mod color;
+pub mod distance_field;
 mod error;
 mod instances;
+mod loader;
+mod palette;
 mod pass;
 mod pipeline;
+mod readback;
 pub mod sprite;
 mod sprite_buffers;
 mod texture;
 
 use crate::renderer::camera::Camera;
+use image::io::Reader as ImageReader;
+use image::{DynamicImage, RgbaImage};
 use pollster::FutureExt;
 use std::collections::{HashMap, HashSet};
 use std::iter;
@@ -17,28 +23,42 @@ use wgpu::util::DeviceExt;
 use wgpu::{CommandEncoder, TextureView};
 use winit::window::Window;
 
-use crate::renderer::color::Color;
+use crate::renderer::color::{Color, CornerColors};
+use crate::renderer::distance_field::{DistanceField, DistanceFieldEffects};
 pub use crate::renderer::error::Error;
 use crate::renderer::instances::Instance;
+pub use crate::renderer::loader::{LoadingProgress, SpriteLoader};
+pub use crate::renderer::palette::Palette;
 use crate::renderer::pass::PassDescriptor;
 use crate::renderer::pipeline::Pipeline;
 use crate::renderer::sprite::Sprite;
 use crate::renderer::sprite_buffers::SpriteBuffers;
 pub use crate::renderer::texture::Texture;
+use crate::renderer::texture::TextureKind;
 
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 pub struct Layer(pub isize);
 
 pub type TextureRef = Rc<Texture>;
 
+/// Copy of the last presented frame, kept when frame readback is enabled.
+#[derive(Debug)]
+struct FrameCapture {
+    texture: wgpu::Texture,
+    captured: bool,
+}
+
 #[derive(Debug)]
 pub struct Renderer {
     device: wgpu::Device,
     queue: wgpu::Queue,
     target_surface: wgpu::Surface,
+    surface_config: wgpu::SurfaceConfiguration,
+    frame_capture: Option<FrameCapture>,
     sprite_buffers: SpriteBuffers,
     pipeline: Pipeline,
     camera: Camera,
+    default_palette: Texture,
     texture_id: usize,
 }
 
@@ -68,9 +88,17 @@ impl Renderer {
             )
             .block_on()?;
 
+        // sRGB surfaces encode linear shader output themselves; other formats make the shader
+        // encode it, see `Pipeline::create`
+        let supported_formats = surface.get_supported_formats(&adapter);
+        let format = supported_formats
+            .iter()
+            .copied()
+            .find(|format| format.describe().srgb)
+            .unwrap_or(supported_formats[0]);
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface.get_supported_formats(&adapter)[0],
+            format,
             width: window.inner_size().width,
             height: window.inner_size().height,
             present_mode: Default::default(),
@@ -82,14 +110,25 @@ impl Renderer {
         let screen_size = (config.width, config.height).into();
         let pipeline = Pipeline::create(&device, config.format);
         let camera = Camera::new(&device, screen_size, &pipeline.bind_group_layouts.camera);
+        // bound in passes which do not use palettes, never sampled
+        let default_palette = Texture::create_blank(
+            (1, 1).into(),
+            &device,
+            &pipeline.bind_group_layouts.texture,
+            usize::MAX,
+            TextureKind::Color,
+        );
 
         Ok(Renderer {
             device,
             queue,
             target_surface: surface,
+            surface_config: config,
+            frame_capture: None,
             sprite_buffers,
             pipeline,
             camera,
+            default_palette,
             texture_id: 0,
         })
     }
@@ -105,22 +144,224 @@ impl Renderer {
             textures: HashMap::new(),
         };
         render_closure(&mut ctx);
-        ctx.render()
+        ctx.render()?;
+
+        if let Some(capture) = &mut self.frame_capture {
+            capture.captured = true;
+        }
+        Ok(())
     }
 
     pub fn load_sprite(&mut self, file_path: &str) -> Result<Sprite, Error> {
+        self.load_sprite_of_kind(file_path, TextureKind::Color)
+    }
+
+    /// Loads an image of a distance field, like a glyph atlas of an SDF or MSDF font.
+    /// The sprite is drawn with the distance field shader.
+    #[allow(dead_code)]
+    pub fn load_distance_field_sprite(
+        &mut self,
+        file_path: &str,
+        distance_field: DistanceField,
+    ) -> Result<Sprite, Error> {
+        let sprite = self.load_sprite_of_kind(file_path, TextureKind::Data)?;
+        Ok(sprite.with_distance_field(distance_field))
+    }
+
+    fn load_sprite_of_kind(&mut self, file_path: &str, kind: TextureKind) -> Result<Sprite, Error> {
         let texture = Texture::load_from_file(
             file_path,
             &self.device,
             &self.queue,
             &self.pipeline.bind_group_layouts.texture,
             self.texture_id,
+            kind,
         )?;
         let texture_ref = Rc::new(texture);
         self.texture_id += 1;
 
         Ok(Sprite::from_whole_texture(&texture_ref))
     }
+
+    #[allow(dead_code)]
+    pub fn load_sprite_from_image(&mut self, image: DynamicImage) -> Sprite {
+        Sprite::from_whole_texture(&self.create_texture(image))
+    }
+
+    /// Loads an image whose every row is a separate palette of colors.
+    #[allow(dead_code)]
+    pub fn load_palettes(&mut self, file_path: &str) -> Result<Vec<Palette>, Error> {
+        let image = ImageReader::open(file_path)?
+            .decode()
+            .map_err(|_| Error::TextureEncodingError)?;
+        Ok(Palette::from_rows(&self.create_texture(image)))
+    }
+
+    #[allow(dead_code)]
+    pub fn create_palettes(&mut self, palettes: &[Vec<Color>]) -> Vec<Palette> {
+        let width = palettes.iter().map(Vec::len).max().unwrap_or(0).max(1);
+        let mut image = RgbaImage::new(width as u32, palettes.len().max(1) as u32);
+        for (row, palette) in palettes.iter().enumerate() {
+            for (id, color) in palette.iter().enumerate() {
+                image.put_pixel(id as u32, row as u32, image::Rgba(color.to_rgba8()));
+            }
+        }
+        let mut created = Palette::from_rows(&self.create_texture(DynamicImage::from(image)));
+        // an empty list still needs a texture of at least one row
+        created.truncate(palettes.len());
+        created
+    }
+
+    #[allow(dead_code)]
+    pub fn load_sprites_in_background(&mut self, file_paths: &[&str]) -> SpriteLoader {
+        SpriteLoader::spawn(file_paths)
+    }
+
+    /// Uploads images decoded so far by the loader's workers; call it once per frame.
+    #[allow(dead_code)]
+    pub fn upload_loaded(&mut self, loader: &mut SpriteLoader) -> LoadingProgress {
+        loader.upload_pending(self);
+        loader.progress()
+    }
+
+    /// Creates a sprite backed by a new, fully transparent texture, which can be
+    /// painted later with `update_sprite_pixels`.
+    #[allow(dead_code)]
+    pub fn create_dynamic_sprite(&mut self, size: cgmath::Vector2<u32>) -> Sprite {
+        let texture = Texture::create_blank(
+            size,
+            &self.device,
+            &self.pipeline.bind_group_layouts.texture,
+            self.texture_id,
+            TextureKind::Color,
+        );
+        texture.write_pixels(&self.queue, (0, 0).into(), &RgbaImage::new(size.x, size.y));
+        self.texture_id += 1;
+
+        Sprite::from_whole_texture(&Rc::new(texture))
+    }
+
+    /// Overwrites pixels of the sprite starting at `offset`, relative to the sprite's
+    /// top-left corner. Changes become visible in the next rendered frame; the CPU copy of
+    /// the pixels, if the texture keeps one, is updated right away.
+    #[allow(dead_code)]
+    pub fn update_sprite_pixels(
+        &self,
+        sprite: &Sprite,
+        offset: cgmath::Vector2<u32>,
+        pixels: &RgbaImage,
+    ) -> Result<(), Error> {
+        let (width, height) = pixels.dimensions();
+        if !sprite.contains_region(offset, (width, height).into()) {
+            return Err(Error::PixelsOutOfBounds);
+        }
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        sprite
+            .texture
+            .write_pixels(&self.queue, sprite.offset + offset, pixels);
+        Ok(())
+    }
+
+    /// Copies pixels of the texture from the GPU. Blocks until the copy is finished.
+    #[allow(dead_code)]
+    pub fn read_texture(&self, texture: &Texture) -> Result<RgbaImage, Error> {
+        readback::read_texture(
+            &self.device,
+            &self.queue,
+            &texture.texture,
+            texture.size,
+            texture.kind.format(),
+        )
+    }
+
+    #[allow(dead_code)]
+    pub fn read_sprite(&self, sprite: &Sprite) -> Result<RgbaImage, Error> {
+        let texture_image = self.read_texture(&sprite.texture)?;
+        let sprite_image = image::imageops::crop_imm(
+            &texture_image,
+            sprite.offset.x,
+            sprite.offset.y,
+            sprite.size.x,
+            sprite.size.y,
+        );
+        Ok(sprite_image.to_image())
+    }
+
+    /// When enabled, every presented frame is also copied to a texture, so it can be read
+    /// with `read_last_frame`.
+    #[allow(dead_code)]
+    pub fn set_frame_readback(&mut self, enabled: bool) {
+        if enabled == self.frame_capture.is_some() {
+            return;
+        }
+
+        self.surface_config.usage = if enabled {
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC
+        } else {
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+        };
+        self.target_surface
+            .configure(&self.device, &self.surface_config);
+
+        self.frame_capture = enabled.then(|| FrameCapture {
+            texture: self.device.create_texture(&wgpu::TextureDescriptor {
+                size: self.surface_extent(),
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: self.surface_config.format,
+                usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::COPY_SRC,
+                label: Some("Frame capture"),
+            }),
+            captured: false,
+        });
+    }
+
+    #[allow(dead_code)]
+    pub fn read_last_frame(&self) -> Result<RgbaImage, Error> {
+        match &self.frame_capture {
+            Some(capture) if capture.captured => readback::read_texture(
+                &self.device,
+                &self.queue,
+                &capture.texture,
+                (self.surface_config.width, self.surface_config.height).into(),
+                self.surface_config.format,
+            ),
+            _ => Err(Error::NoFrameCaptured),
+        }
+    }
+
+    /// Color in the space expected by the surface, for values written to it directly.
+    fn surface_color(&self, color: Color) -> wgpu::Color {
+        if self.surface_config.format.describe().srgb {
+            color.to_linear().into()
+        } else {
+            color.into()
+        }
+    }
+
+    fn surface_extent(&self) -> wgpu::Extent3d {
+        wgpu::Extent3d {
+            width: self.surface_config.width,
+            height: self.surface_config.height,
+            depth_or_array_layers: 1,
+        }
+    }
+
+    fn create_texture(&mut self, image: DynamicImage) -> TextureRef {
+        let texture = Texture::load_from_image(
+            image,
+            &self.device,
+            &self.queue,
+            &self.pipeline.bind_group_layouts.texture,
+            self.texture_id,
+            TextureKind::Color,
+        );
+        self.texture_id += 1;
+        Rc::new(texture)
+    }
 }
 
 #[derive(Debug)]
@@ -144,21 +385,105 @@ impl<'a> RenderContext<'a> {
         layer: Layer,
         color: Color,
     ) {
+        self.push_instance(sprite, position, layer, color.into(), 1.0, None);
+    }
+
+    /// Draws a sprite scaled around its pivot.
+    #[allow(dead_code)]
+    pub fn draw_sprite_scaled(
+        &mut self,
+        sprite: &Sprite,
+        position: cgmath::Vector2<i32>,
+        layer: Layer,
+        color: Color,
+        scale: f32,
+    ) {
+        self.push_instance(sprite, position, layer, color.into(), scale, None);
+    }
+
+    /// Draws a sprite of a distance field texture with an outline or a shadow.
+    /// Sprites of other textures are drawn without the effects.
+    #[allow(dead_code)]
+    pub fn draw_sprite_with_effects(
+        &mut self,
+        sprite: &Sprite,
+        position: cgmath::Vector2<i32>,
+        layer: Layer,
+        color: Color,
+        scale: f32,
+        effects: &DistanceFieldEffects,
+    ) {
+        let instance = self.push_instance(sprite, position, layer, color.into(), scale, None);
+        if let Some((_, instance_effects)) = &mut instance.distance_field {
+            *instance_effects = *effects;
+        }
+    }
+
+    /// Draws a sprite tinted with colors interpolated between its corners.
+    #[allow(dead_code)]
+    pub fn draw_sprite_with_gradient(
+        &mut self,
+        sprite: &Sprite,
+        position: cgmath::Vector2<i32>,
+        layer: Layer,
+        colors: CornerColors,
+    ) {
+        self.push_instance(sprite, position, layer, colors, 1.0, None);
+    }
+
+    /// Draws an indexed-color sprite, taking its colors from the palette.
+    #[allow(dead_code)]
+    pub fn draw_sprite_with_palette(
+        &mut self,
+        sprite: &Sprite,
+        palette: &Palette,
+        position: cgmath::Vector2<i32>,
+        layer: Layer,
+        color: Color,
+    ) {
+        self.push_instance(sprite, position, layer, color.into(), 1.0, Some(palette));
+    }
+
+    fn push_instance(
+        &mut self,
+        sprite: &Sprite,
+        position: cgmath::Vector2<i32>,
+        layer: Layer,
+        colors: CornerColors,
+        scale: f32,
+        palette: Option<&Palette>,
+    ) -> &mut Instance {
         let texture = &sprite.texture;
         if !self.textures.contains_key(&texture.id) {
             self.textures.insert(texture.id, texture.clone());
         }
+        if let Some(palette) = palette {
+            self.textures
+                .entry(palette.texture.id)
+                .or_insert_with(|| palette.texture.clone());
+        }
 
         self.instances.push(Instance {
-            position: (position.x as f32, position.y as f32).into(),
+            position: (
+                position.x as f32 - sprite.pivot.x as f32 * scale,
+                position.y as f32 - sprite.pivot.y as f32 * scale,
+            )
+                .into(),
             texture_id: texture.id,
             layer,
 
             tex_size: texture.size,
             sprite_size: sprite.size,
             sprite_offset: sprite.offset,
-            color,
-        })
+            uv_inset: sprite.uv_inset,
+            scale,
+            colors,
+            palette: palette.map(|p| (p.texture.id, p.row)),
+            distance_field: sprite
+                .distance_field
+                .map(|field| (field, DistanceFieldEffects::default())),
+        });
+        self.instances.last_mut().unwrap()
     }
 
     fn render(&self) -> Result<(), Error> {
@@ -185,6 +510,14 @@ impl<'a> RenderContext<'a> {
             self.encode_pass(&view, &mut encoder, &pass_descriptor, id);
         }
 
+        if let Some(capture) = &self.renderer.frame_capture {
+            encoder.copy_texture_to_texture(
+                output.texture.as_image_copy(),
+                capture.texture.as_image_copy(),
+                self.renderer.surface_extent(),
+            );
+        }
+
         self.renderer.queue.submit(iter::once(encoder.finish()));
         output.present();
         Ok(())
@@ -199,7 +532,7 @@ impl<'a> RenderContext<'a> {
     ) {
         let (instances_buffer, instances_count) = self.get_raw_instances_for_pass(pass_descriptor);
         let load_op: wgpu::LoadOp<wgpu::Color> = if id == 0 {
-            wgpu::LoadOp::Clear(self.clear_color.into())
+            wgpu::LoadOp::Clear(self.renderer.surface_color(self.clear_color))
         } else {
             wgpu::LoadOp::Load
         };
@@ -215,13 +548,22 @@ impl<'a> RenderContext<'a> {
             })],
             depth_stencil_attachment: None,
         });
-        render_pass.set_pipeline(&self.renderer.pipeline.pipeline);
+        let pipeline = match pass_descriptor.distance_field {
+            true => &self.renderer.pipeline.distance_field_pipeline,
+            false => &self.renderer.pipeline.pipeline,
+        };
+        render_pass.set_pipeline(pipeline);
         render_pass.set_bind_group(0, &self.renderer.camera.bind_group, &[]);
         render_pass.set_bind_group(
             1,
             &self.textures[&pass_descriptor.texture_id].bind_group,
             &[],
         );
+        let palette = match pass_descriptor.palette_texture_id {
+            Some(id) => &self.textures[&id],
+            None => &self.renderer.default_palette,
+        };
+        render_pass.set_bind_group(2, &palette.bind_group, &[]);
         render_pass.set_vertex_buffer(0, self.renderer.sprite_buffers.vertex.slice(..));
         render_pass.set_vertex_buffer(1, instances_buffer.slice(..));
         render_pass.set_index_buffer(
@@ -235,22 +577,33 @@ impl<'a> RenderContext<'a> {
         &self,
         pass_descriptor: &PassDescriptor,
     ) -> (wgpu::Buffer, usize) {
-        let raw_instances = self
+        let instances = self
             .instances
             .iter()
-            .filter(|i| i.to_pass_descriptor() == *pass_descriptor)
-            .map(|i| i.to_raw())
-            .collect::<Vec<_>>();
+            .filter(|i| i.to_pass_descriptor() == *pass_descriptor);
+        let (contents, count): (Vec<u8>, usize) = if pass_descriptor.distance_field {
+            let raw_instances: Vec<_> = instances.map(|i| i.to_distance_field_raw()).collect();
+            (
+                bytemuck::cast_slice(&raw_instances).to_vec(),
+                raw_instances.len(),
+            )
+        } else {
+            let raw_instances: Vec<_> = instances.map(|i| i.to_raw()).collect();
+            (
+                bytemuck::cast_slice(&raw_instances).to_vec(),
+                raw_instances.len(),
+            )
+        };
         let instances_buffer_desc = wgpu::util::BufferInitDescriptor {
             label: Some("Instances Buffer"),
-            contents: bytemuck::cast_slice(&raw_instances),
+            contents: &contents,
             usage: wgpu::BufferUsages::VERTEX,
         };
         (
             self.renderer
                 .device
                 .create_buffer_init(&instances_buffer_desc),
-            raw_instances.len(),
+            count,
         )
     }
 }
@@ -0,0 +1,28 @@
+use crate::renderer::TextureRef;
+
+/// Row of a lookup texture used to recolor indexed-color sprites.
+///
+/// Red channel of an indexed sprite's pixel holds the index of a color in the palette,
+/// while its alpha channel is multiplied by the alpha of that color.
+#[derive(Debug, Clone)]
+pub struct Palette {
+    pub(in crate::renderer) texture: TextureRef,
+    pub(in crate::renderer) row: u32,
+}
+
+impl Palette {
+    /// Every row of the texture becomes a separate palette.
+    pub fn from_rows(texture: &TextureRef) -> Vec<Palette> {
+        (0..texture.size.y)
+            .map(|row| Palette {
+                texture: texture.clone(),
+                row,
+            })
+            .collect()
+    }
+
+    #[allow(dead_code)]
+    pub fn get_size(&self) -> u32 {
+        self.texture.size.x
+    }
+}
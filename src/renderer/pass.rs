@@ -4,4 +4,7 @@ use crate::renderer::Layer;
 pub struct PassDescriptor {
     pub texture_id: usize,
     pub layer: Layer,
+    pub palette_texture_id: Option<usize>,
+    /// Drawn with the distance field pipeline.
+    pub distance_field: bool,
 }
@@ -48,14 +48,17 @@ impl BindGroupLayouts {
         BindGroupLayouts { camera, texture }
     }
 
-    fn as_array(&self) -> [&wgpu::BindGroupLayout; 2] {
-        [&self.camera, &self.texture]
+    fn as_array(&self) -> [&wgpu::BindGroupLayout; 3] {
+        // palettes are bound the same way as sprite textures
+        [&self.camera, &self.texture, &self.texture]
     }
 }
 
 #[derive(Debug)]
 pub(in crate::renderer) struct Pipeline {
     pub(in crate::renderer) pipeline: wgpu::RenderPipeline,
+    /// Draws sprites of distance field textures.
+    pub(in crate::renderer) distance_field_pipeline: wgpu::RenderPipeline,
     pub(in crate::renderer) bind_group_layouts: BindGroupLayouts,
 }
 
@@ -64,9 +67,6 @@ impl Pipeline {
         device: &wgpu::Device,
         target_format: wgpu::TextureFormat,
     ) -> Pipeline {
-        let shader_descriptor = include_wgsl!("shader.wgsl");
-        let shader = device.create_shader_module(shader_descriptor);
-
         let bind_group_layouts = BindGroupLayouts::create(device);
         let layout_descriptor = wgpu::PipelineLayoutDescriptor {
             label: Some("Pipeline layout"),
@@ -75,18 +75,58 @@ impl Pipeline {
         };
         let layout = device.create_pipeline_layout(&layout_descriptor);
 
+        let shader = device.create_shader_module(include_wgsl!("shader.wgsl"));
+        let instances_buf_desc = wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<[f32; 31]>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &wgpu::vertex_attr_array![2 => Float32x3, 3 => Float32x3, 4 => Float32x3, 5 => Float32x2, 6 => Float32x2, 7 => Float32x4, 8 => Float32x4, 9 => Float32x4, 10 => Float32x4, 11 => Float32x2],
+        };
+        let pipeline = Self::create_render_pipeline(
+            device,
+            &layout,
+            &shader,
+            instances_buf_desc,
+            target_format,
+            "Render pipeline",
+        );
+
+        let distance_field_shader =
+            device.create_shader_module(include_wgsl!("distance_field.wgsl"));
+        let distance_field_instances_buf_desc = wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<[f32; 32]>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &wgpu::vertex_attr_array![2 => Float32x3, 3 => Float32x3, 4 => Float32x3, 5 => Float32x2, 6 => Float32x2, 7 => Float32x4, 8 => Float32x4, 9 => Float32x4, 10 => Float32x2, 11 => Float32x4, 12 => Float32],
+        };
+        let distance_field_pipeline = Self::create_render_pipeline(
+            device,
+            &layout,
+            &distance_field_shader,
+            distance_field_instances_buf_desc,
+            target_format,
+            "Distance field render pipeline",
+        );
+
+        Pipeline {
+            pipeline,
+            distance_field_pipeline,
+            bind_group_layouts,
+        }
+    }
+
+    fn create_render_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        instances_buf_desc: wgpu::VertexBufferLayout,
+        target_format: wgpu::TextureFormat,
+        label: &str,
+    ) -> wgpu::RenderPipeline {
         let vertex_buf_desc = wgpu::VertexBufferLayout {
             array_stride: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
             step_mode: wgpu::VertexStepMode::Vertex,
             attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2],
         };
 
-        let instances_buf_desc = wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<[f32; 17]>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Instance,
-            attributes: &wgpu::vertex_attr_array![2 => Float32x3, 3 => Float32x3, 4 => Float32x3, 5 => Float32x2, 6 => Float32x2, 7 => Float32x4],
-        };
-
         let blend_state = wgpu::BlendState {
             color: wgpu::BlendComponent {
                 src_factor: wgpu::BlendFactor::SrcAlpha,
@@ -103,16 +143,20 @@ impl Pipeline {
         })];
 
         let pipeline_descriptor = wgpu::RenderPipelineDescriptor {
-            label: Some("Render pipeline"),
-            layout: Some(&layout),
+            label: Some(label),
+            layout: Some(layout),
             vertex: wgpu::VertexState {
-                module: &shader,
+                module: shader,
                 entry_point: "vertex",
                 buffers: &[vertex_buf_desc, instances_buf_desc],
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fragment",
+                module: shader,
+                entry_point: if target_format.describe().srgb {
+                    "fragment"
+                } else {
+                    "fragment_srgb_encode"
+                },
                 targets: &fragment_targets,
             }),
             primitive: wgpu::PrimitiveState {
@@ -132,10 +176,6 @@ impl Pipeline {
             },
             multiview: None,
         };
-        let pipeline = device.create_render_pipeline(&pipeline_descriptor);
-        Pipeline {
-            pipeline,
-            bind_group_layouts,
-        }
+        device.create_render_pipeline(&pipeline_descriptor)
     }
 }
@@ -15,13 +15,23 @@ struct InstanceInput {
     @location(4) matrix_3: vec3<f32>,
     @location(5) tex_lower_bounds: vec2<f32>,
     @location(6) tex_higher_bounds: vec2<f32>,
-    @location(7) color: vec4<f32>
+    @location(7) color_bottom_left: vec4<f32>,
+    @location(8) color_bottom_right: vec4<f32>,
+    @location(9) color_top_right: vec4<f32>,
+    @location(10) color_top_left: vec4<f32>,
+    @location(11) palette: vec2<f32>
 }
 
 struct VertexOutput {
     @builtin(position) clip_pos: vec4<f32>,
     @location(0) tex: vec2<f32>,
-    @location(1) color: vec4<f32>
+    @location(1) palette: vec2<f32>,
+    // position inside the sprite, used to interpolate between corner colors
+    @location(2) local_pos: vec2<f32>,
+    @location(3) @interpolate(flat) color_bottom_left: vec4<f32>,
+    @location(4) @interpolate(flat) color_bottom_right: vec4<f32>,
+    @location(5) @interpolate(flat) color_top_right: vec4<f32>,
+    @location(6) @interpolate(flat) color_top_left: vec4<f32>
 }
 
 fn real_tex_coords(vertex: VertexInput, instance: InstanceInput) -> vec2<f32> {
@@ -55,7 +65,12 @@ fn vertex(
     out.clip_pos = camera.view_proj * out.clip_pos;
 
     out.tex = real_tex_coords(model, instance);
-    out.color = instance.color;
+    out.palette = instance.palette;
+    out.local_pos = model.pos;
+    out.color_bottom_left = instance.color_bottom_left;
+    out.color_bottom_right = instance.color_bottom_right;
+    out.color_top_right = instance.color_top_right;
+    out.color_top_left = instance.color_top_left;
 
     return out;
 }
@@ -65,8 +80,43 @@ var texture: texture_2d<f32>;
 @group(1) @binding(1)
 var sampler_: sampler;
 
+@group(2) @binding(0)
+var palette_texture: texture_2d<f32>;
+
+fn srgb_encode(linear: f32) -> f32 {
+    if (linear <= 0.0031308) {
+        return linear * 12.92;
+    }
+    return 1.055 * pow(linear, 1.0 / 2.4) - 0.055;
+}
+
+// sRGB textures are decoded on sampling, so indices stored in them have to be encoded back
+fn palette_index(linear: f32) -> i32 {
+    return i32(round(srgb_encode(linear) * 255.0));
+}
+
+fn shade(in: VertexOutput) -> vec4<f32> {
+    var sampled = textureSample(texture, sampler_, in.tex);
+    if (in.palette.x > 0.5) {
+        let index = palette_index(sampled.r);
+        let palette_color = textureLoad(palette_texture, vec2<i32>(index, i32(in.palette.y)), 0);
+        sampled = vec4<f32>(palette_color.rgb, palette_color.a * sampled.a);
+    }
+    let bottom_color = mix(in.color_bottom_left, in.color_bottom_right, in.local_pos.x);
+    let top_color = mix(in.color_top_left, in.color_top_right, in.local_pos.x);
+    var color = sampled * mix(bottom_color, top_color, in.local_pos.y);
+    return color;
+}
+
+// for sRGB surfaces, which encode linear colors on write
 @fragment
 fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
-    var color = textureSample(texture, sampler_, in.tex) * in.color;
-    return color;
-}
\ No newline at end of file
+    return shade(in);
+}
+
+// for other surfaces, which store written values as they are
+@fragment
+fn fragment_srgb_encode(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = shade(in);
+    return vec4<f32>(srgb_encode(color.r), srgb_encode(color.g), srgb_encode(color.b), color.a);
+}
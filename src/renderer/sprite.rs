@@ -1,11 +1,20 @@
+use crate::renderer::distance_field::DistanceField;
+use crate::renderer::Error;
 use crate::TextureRef;
 use cgmath::Vector2;
+use image::RgbaImage;
 
 #[derive(Debug, Clone)]
 pub struct Sprite {
     pub(in crate::renderer) texture: TextureRef,
     pub(in crate::renderer) size: Vector2<u32>,
     pub(in crate::renderer) offset: Vector2<u32>,
+    /// Point of the sprite, relative to its bottom-left corner, placed at the drawing position.
+    pub(in crate::renderer) pivot: Vector2<i32>,
+    /// Distance in texels by which sampled texture coordinates are moved inside the sprite.
+    pub(in crate::renderer) uv_inset: f32,
+    /// Set for sprites of distance field textures, which are drawn with their own shader.
+    pub(in crate::renderer) distance_field: Option<DistanceField>,
 }
 
 impl Sprite {
@@ -14,14 +23,67 @@ impl Sprite {
             texture: texture.clone(),
             offset: (0, 0).into(),
             size: texture.size,
+            pivot: (0, 0).into(),
+            uv_inset: 0.0,
+            distance_field: None,
         }
     }
 
-    pub fn slice(&self, size: Vector2<u32>, offset: Vector2<u32>) -> Sprite {
-        Sprite {
+    /// Cuts a part of the sprite out, with `offset` relative to the sprite's top-left corner.
+    pub fn slice(&self, size: Vector2<u32>, offset: Vector2<u32>) -> Result<Sprite, Error> {
+        if !self.contains_region(offset, size) {
+            return Err(Error::SliceOutOfBounds {
+                sprite_size: self.size,
+                slice_size: size,
+                slice_offset: offset,
+            });
+        }
+
+        Ok(Sprite {
             texture: self.texture.clone(),
             size,
-            offset,
+            offset: self.offset + offset,
+            pivot: (0, 0).into(),
+            uv_inset: self.uv_inset,
+            distance_field: self.distance_field,
+        })
+    }
+
+    /// Whether a region at `offset`, relative to the sprite's top-left corner, lies inside it.
+    pub(in crate::renderer) fn contains_region(
+        &self,
+        offset: Vector2<u32>,
+        size: Vector2<u32>,
+    ) -> bool {
+        let fits = |offset: u32, size: u32, parent_size: u32| {
+            offset
+                .checked_add(size)
+                .is_some_and(|end| end <= parent_size)
+        };
+        fits(offset.x, size.x, self.size.x) && fits(offset.y, size.y, self.size.y)
+    }
+
+    pub fn with_pivot(&self, pivot: Vector2<i32>) -> Sprite {
+        Sprite {
+            pivot,
+            ..self.clone()
+        }
+    }
+
+    /// Moves texture coordinates inside the sprite, so neighbouring atlas cells do not bleed
+    /// in when the sprite is scaled or drawn between pixels. Half a texel is usually enough.
+    #[allow(dead_code)]
+    pub fn with_uv_inset(&self, uv_inset: f32) -> Sprite {
+        Sprite {
+            uv_inset,
+            ..self.clone()
+        }
+    }
+
+    pub fn with_distance_field(&self, distance_field: DistanceField) -> Sprite {
+        Sprite {
+            distance_field: Some(distance_field),
+            ..self.clone()
         }
     }
 
@@ -32,4 +94,24 @@ impl Sprite {
     pub fn get_offset(&self) -> Vector2<u32> {
         self.offset
     }
+
+    /// Pixels of the sprite, if its texture keeps a CPU copy of them.
+    #[allow(dead_code)]
+    pub fn get_pixels(&self) -> Option<RgbaImage> {
+        self.texture.pixels.borrow().as_ref().map(|pixels| {
+            image::imageops::crop_imm(
+                pixels,
+                self.offset.x,
+                self.offset.y,
+                self.size.x,
+                self.size.y,
+            )
+            .to_image()
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn get_pivot(&self) -> Vector2<i32> {
+        self.pivot
+    }
 }
@@ -1,15 +1,50 @@
 use crate::renderer::Error;
 use cgmath::Vector2;
 use image::io::Reader as ImageReader;
-use image::{DynamicImage, GenericImageView};
+use image::{imageops, DynamicImage, GenericImageView, RgbaImage};
+use std::cell::RefCell;
+
+pub const TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+pub const DATA_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// What texels of a texture store, which decides how they are decoded and sampled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum TextureKind {
+    /// sRGB colors, sampled without filtering to keep pixel art crisp.
+    #[default]
+    Color,
+    /// Linear values like distance fields, filtered between texels.
+    Data,
+}
+
+impl TextureKind {
+    pub fn format(self) -> wgpu::TextureFormat {
+        match self {
+            TextureKind::Color => TEXTURE_FORMAT,
+            TextureKind::Data => DATA_TEXTURE_FORMAT,
+        }
+    }
+
+    fn filter_mode(self) -> wgpu::FilterMode {
+        match self {
+            TextureKind::Color => wgpu::FilterMode::Nearest,
+            TextureKind::Data => wgpu::FilterMode::Linear,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Texture {
+    pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
     pub size: Vector2<u32>,
     pub bind_group: wgpu::BindGroup,
     pub id: usize,
+    pub kind: TextureKind,
+    /// CPU copy of pixels of textures loaded from images; dynamic textures do not keep one.
+    /// Kept in sync by `write_pixels`.
+    pub pixels: RefCell<Option<RgbaImage>>,
 }
 
 impl Texture {
@@ -19,6 +54,7 @@ impl Texture {
         queue: &wgpu::Queue,
         layout: &wgpu::BindGroupLayout,
         id: usize,
+        kind: TextureKind,
     ) -> Result<Texture, Error> {
         let image = ImageReader::open(file_name)?;
         let decoded_image = image.decode().map_err(|_| Error::TextureEncodingError)?;
@@ -28,6 +64,7 @@ impl Texture {
             queue,
             layout,
             id,
+            kind,
         ))
     }
 
@@ -37,13 +74,25 @@ impl Texture {
         queue: &wgpu::Queue,
         layout: &wgpu::BindGroupLayout,
         id: usize,
+        kind: TextureKind,
     ) -> Texture {
         let image_rgba = image.to_rgba8();
+        let mut texture = Self::create_blank(image.dimensions().into(), device, layout, id, kind);
+        texture.write_pixels(queue, (0, 0).into(), &image_rgba);
+        *texture.pixels.get_mut() = Some(image_rgba);
+        texture
+    }
 
-        let dimensions = image.dimensions();
+    pub fn create_blank(
+        size: Vector2<u32>,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        id: usize,
+        kind: TextureKind,
+    ) -> Texture {
         let texture_size = wgpu::Extent3d {
-            width: dimensions.0,
-            height: dimensions.1,
+            width: size.x,
+            height: size.y,
             depth_or_array_layers: 1,
         };
 
@@ -52,35 +101,21 @@ impl Texture {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            format: kind.format(),
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC,
             label: Some("texture"),
         };
         let texture = device.create_texture(&texture_descriptor);
 
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &image_rgba,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: std::num::NonZeroU32::new(4 * dimensions.0),
-                rows_per_image: std::num::NonZeroU32::new(dimensions.1),
-            },
-            texture_size,
-        );
-
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
+            mag_filter: kind.filter_mode(),
+            min_filter: kind.filter_mode(),
             mipmap_filter: wgpu::FilterMode::Nearest,
             ..Default::default()
         });
@@ -101,11 +136,46 @@ impl Texture {
         });
 
         Texture {
+            texture,
             view,
             sampler,
             bind_group,
-            size: dimensions.into(),
+            size,
             id,
+            kind,
+            pixels: RefCell::new(None),
+        }
+    }
+
+    /// Overwrites a rectangle of the texture starting at `offset` with the pixels of `image`,
+    /// in its CPU copy too. The rectangle has to fit inside the texture.
+    pub fn write_pixels(&self, queue: &wgpu::Queue, offset: Vector2<u32>, image: &RgbaImage) {
+        let (width, height) = image.dimensions();
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: offset.x,
+                    y: offset.y,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            image,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(4 * width),
+                rows_per_image: std::num::NonZeroU32::new(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        if let Some(pixels) = self.pixels.borrow_mut().as_mut() {
+            imageops::replace(pixels, image, offset.x as i64, offset.y as i64);
         }
     }
 }
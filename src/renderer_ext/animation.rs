@@ -0,0 +1,299 @@
+use crate::renderer::Error;
+use crate::Sprite;
+use chrono::Duration;
+use std::collections::HashMap;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum PlaybackMode {
+    #[default]
+    Loop,
+    PingPong,
+    Once,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationFrame {
+    pub sprite: Sprite,
+    pub duration: Duration,
+    /// Event reported by `AnimatedSprite::update` each time the frame is entered.
+    pub event: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AnimationClip {
+    frames: Vec<AnimationFrame>,
+    mode: PlaybackMode,
+}
+
+impl AnimationClip {
+    pub fn new(mode: PlaybackMode) -> AnimationClip {
+        AnimationClip {
+            frames: vec![],
+            mode,
+        }
+    }
+
+    /// Clip in which every sprite is shown for the same time.
+    pub fn from_sprites(
+        sprites: &[Sprite],
+        duration: Duration,
+        mode: PlaybackMode,
+    ) -> AnimationClip {
+        sprites
+            .iter()
+            .fold(AnimationClip::new(mode), |clip, sprite| {
+                clip.with_frame(sprite, duration)
+            })
+    }
+
+    pub fn with_frame(mut self, sprite: &Sprite, duration: Duration) -> AnimationClip {
+        self.frames.push(AnimationFrame {
+            sprite: sprite.clone(),
+            duration,
+            event: None,
+        });
+        self
+    }
+
+    /// Sets the event of the frame; frames which are not in the clip are ignored.
+    pub fn with_event(mut self, frame_id: usize, event: &str) -> AnimationClip {
+        if let Some(frame) = self.frames.get_mut(frame_id) {
+            frame.event = Some(event.to_string());
+        }
+        self
+    }
+
+    pub fn frames(&self) -> &[AnimationFrame] {
+        &self.frames
+    }
+
+    pub fn mode(&self) -> PlaybackMode {
+        self.mode
+    }
+
+    /// Time of a single pass through the clip; ping-pong clips pass forth and back.
+    pub fn cycle_duration(&self) -> Duration {
+        self.cycle()
+            .into_iter()
+            .fold(Duration::zero(), |total, id| {
+                total + self.frames[id].duration
+            })
+    }
+
+    pub fn is_finished(&self, elapsed: Duration) -> bool {
+        self.mode == PlaybackMode::Once && elapsed >= self.cycle_duration()
+    }
+
+    pub fn frame_at(&self, elapsed: Duration) -> &AnimationFrame {
+        &self.frames[self.frame_id_of_step(self.step_at(elapsed))]
+    }
+
+    fn cycle(&self) -> Vec<usize> {
+        cycle(self.frames.len(), self.mode)
+    }
+
+    /// Number of frame changes since the clip has started.
+    fn step_at(&self, elapsed: Duration) -> usize {
+        let durations: Vec<Duration> = self.frames.iter().map(|frame| frame.duration).collect();
+        step_at(&durations, self.mode, elapsed)
+    }
+
+    fn frame_id_of_step(&self, step: usize) -> usize {
+        let cycle = self.cycle();
+        cycle[step % cycle.len()]
+    }
+}
+
+/// Frame ids shown during a single pass through a clip.
+fn cycle(frame_count: usize, mode: PlaybackMode) -> Vec<usize> {
+    let forward = 0..frame_count;
+    match mode {
+        PlaybackMode::Loop | PlaybackMode::Once => forward.collect(),
+        PlaybackMode::PingPong => {
+            let backward = (1..frame_count.saturating_sub(1)).rev();
+            forward.chain(backward).collect()
+        }
+    }
+}
+
+/// Number of frame changes since the start of a clip with frames of given durations.
+fn step_at(durations: &[Duration], mode: PlaybackMode, elapsed: Duration) -> usize {
+    let cycle = cycle(durations.len(), mode);
+    let cycle_duration = cycle
+        .iter()
+        .fold(Duration::zero(), |total, id| total + durations[*id]);
+    if cycle_duration <= Duration::zero() || elapsed < Duration::zero() {
+        return 0;
+    }
+
+    let cycle_us = cycle_duration.num_microseconds().unwrap_or(i64::MAX);
+    let elapsed_us = elapsed.num_microseconds().unwrap_or(i64::MAX);
+    let (full_cycles, mut remaining_us) = match mode {
+        PlaybackMode::Once if elapsed_us >= cycle_us => return cycle.len() - 1,
+        _ => (elapsed_us / cycle_us, elapsed_us % cycle_us),
+    };
+
+    let mut step = 0;
+    for id in cycle.iter() {
+        let frame_us = durations[*id].num_microseconds().unwrap_or(i64::MAX);
+        if remaining_us < frame_us {
+            break;
+        }
+        remaining_us -= frame_us;
+        step += 1;
+    }
+    full_cycles as usize * cycle.len() + step
+}
+
+/// Animation player, which plays one of its named clips at a time.
+#[derive(Debug, Clone)]
+pub struct AnimatedSprite {
+    clips: HashMap<String, AnimationClip>,
+    current_clip: String,
+    elapsed: Duration,
+    pending_events: Vec<String>,
+}
+
+impl AnimatedSprite {
+    pub fn new(name: &str, clip: AnimationClip) -> Result<AnimatedSprite, Error> {
+        let mut animated_sprite = AnimatedSprite {
+            clips: HashMap::new(),
+            current_clip: name.to_string(),
+            elapsed: Duration::zero(),
+            pending_events: vec![],
+        };
+        animated_sprite.add_clip(name, clip)?;
+        animated_sprite.restart();
+        Ok(animated_sprite)
+    }
+
+    pub fn add_clip(&mut self, name: &str, clip: AnimationClip) -> Result<(), Error> {
+        if clip.frames.is_empty() {
+            return Err(Error::EmptyAnimation(name.to_string()));
+        }
+        self.clips.insert(name.to_string(), clip);
+        Ok(())
+    }
+
+    /// Switches to the clip and plays it from the beginning, unless it is already playing.
+    pub fn play(&mut self, name: &str) -> Result<(), Error> {
+        if !self.clips.contains_key(name) {
+            return Err(Error::MissingAnimation(name.to_string()));
+        }
+        if self.current_clip != name {
+            self.current_clip = name.to_string();
+            self.restart();
+        }
+        Ok(())
+    }
+
+    pub fn restart(&mut self) {
+        self.elapsed = Duration::zero();
+        self.pending_events = self.clip().frames[0].event.iter().cloned().collect();
+    }
+
+    /// Advances the animation, returning events of all frames entered in the meantime.
+    pub fn update(&mut self, delta: Duration) -> Vec<String> {
+        let clip = &self.clips[&self.current_clip];
+        let previous_step = clip.step_at(self.elapsed);
+        self.elapsed = self.elapsed + delta;
+        let current_step = clip.step_at(self.elapsed);
+
+        let mut events = std::mem::take(&mut self.pending_events);
+        for step in previous_step + 1..=current_step {
+            let frame = &clip.frames[clip.frame_id_of_step(step)];
+            events.extend(frame.event.iter().cloned());
+        }
+        events
+    }
+
+    pub fn current_clip_name(&self) -> &str {
+        &self.current_clip
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.clip().is_finished(self.elapsed)
+    }
+
+    pub fn current_sprite(&self) -> &Sprite {
+        &self.clip().frame_at(self.elapsed).sprite
+    }
+
+    fn clip(&self) -> &AnimationClip {
+        &self.clips[&self.current_clip]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(milliseconds: i64) -> Duration {
+        Duration::milliseconds(milliseconds)
+    }
+
+    #[test]
+    fn cycles_of_modes() {
+        assert_eq!(cycle(3, PlaybackMode::Loop), vec![0, 1, 2]);
+        assert_eq!(cycle(3, PlaybackMode::Once), vec![0, 1, 2]);
+        assert_eq!(cycle(4, PlaybackMode::PingPong), vec![0, 1, 2, 3, 2, 1]);
+        assert_eq!(cycle(2, PlaybackMode::PingPong), vec![0, 1]);
+        assert_eq!(cycle(1, PlaybackMode::PingPong), vec![0]);
+    }
+
+    #[test]
+    fn loop_steps_wrap_around() {
+        let durations = [ms(100), ms(100), ms(100)];
+        let step = |elapsed| step_at(&durations, PlaybackMode::Loop, ms(elapsed));
+        assert_eq!(step(0), 0);
+        assert_eq!(step(99), 0);
+        assert_eq!(step(100), 1);
+        assert_eq!(step(299), 2);
+        assert_eq!(step(300), 3);
+        assert_eq!(step(650), 6);
+        assert_eq!(step(-10), 0);
+    }
+
+    #[test]
+    fn steps_follow_per_frame_durations() {
+        let durations = [ms(50), ms(200), ms(10)];
+        let step = |elapsed| step_at(&durations, PlaybackMode::Loop, ms(elapsed));
+        assert_eq!(step(49), 0);
+        assert_eq!(step(50), 1);
+        assert_eq!(step(249), 1);
+        assert_eq!(step(250), 2);
+        assert_eq!(step(260), 3);
+    }
+
+    #[test]
+    fn ping_pong_steps_go_back() {
+        let durations = [ms(100), ms(100), ms(100)];
+        let steps: Vec<usize> = [0, 100, 200, 300, 400]
+            .into_iter()
+            .map(|elapsed| step_at(&durations, PlaybackMode::PingPong, ms(elapsed)))
+            .collect();
+        assert_eq!(steps, vec![0, 1, 2, 3, 4]);
+
+        let cycle = cycle(durations.len(), PlaybackMode::PingPong);
+        let frames: Vec<usize> = steps.iter().map(|step| cycle[step % cycle.len()]).collect();
+        assert_eq!(frames, vec![0, 1, 2, 1, 0]);
+    }
+
+    #[test]
+    fn once_stops_on_last_frame() {
+        let durations = [ms(100), ms(100)];
+        let step = |elapsed| step_at(&durations, PlaybackMode::Once, ms(elapsed));
+        assert_eq!(step(150), 1);
+        assert_eq!(step(200), 1);
+        assert_eq!(step(10_000), 1);
+    }
+
+    #[test]
+    fn zero_length_clips_stay_on_first_step() {
+        assert_eq!(step_at(&[ms(0), ms(0)], PlaybackMode::Loop, ms(100)), 0);
+    }
+}
@@ -0,0 +1,361 @@
+use crate::renderer::{Error, Renderer};
+use crate::Sprite;
+use cgmath::Vector2;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum TagDirection {
+    #[default]
+    Forward,
+    Reverse,
+    PingPong,
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameTag {
+    pub name: String,
+    /// Indices of the first and the last frame of the tag, inclusive.
+    pub from: usize,
+    pub to: usize,
+    pub direction: TagDirection,
+}
+
+#[derive(Debug, Clone)]
+pub struct AtlasFrame {
+    pub name: String,
+    /// Sprite with its pivot set, so a trimmed frame is drawn where the untrimmed one would be.
+    pub sprite: Sprite,
+    /// Rotated frames are stored in the atlas turned 90 degrees clockwise. Their sprite is
+    /// drawn as stored, with the pivot on the same point of the frame.
+    pub rotated: bool,
+    pub trimmed: bool,
+    /// Size of the frame before trimming.
+    pub source_size: Vector2<u32>,
+    pub duration_ms: Option<u32>,
+}
+
+/// Sprite atlas exported as JSON by TexturePacker or Aseprite, in either the hash
+/// or the array variant.
+#[derive(Debug, Clone, Default)]
+pub struct Atlas {
+    pub frames: Vec<AtlasFrame>,
+    pub tags: HashMap<String, FrameTag>,
+    frame_ids: HashMap<String, usize>,
+}
+
+impl Atlas {
+    /// Loads atlas description and the image it points to, relative to the description.
+    pub fn load(renderer: &mut Renderer, json_path: &str) -> Result<Atlas, Error> {
+        let json = fs::read_to_string(json_path)?;
+        let atlas_json = parse_json(&json)?;
+        let image_path = Path::new(json_path).with_file_name(&atlas_json.meta.image);
+        let sprite = renderer.load_sprite(&image_path.to_string_lossy())?;
+        Self::from_json(&sprite, atlas_json)
+    }
+
+    pub fn parse(sprite: &Sprite, json: &str) -> Result<Atlas, Error> {
+        Self::from_json(sprite, parse_json(json)?)
+    }
+
+    pub fn frame(&self, name: &str) -> Result<&AtlasFrame, Error> {
+        self.frame_ids
+            .get(name)
+            .map(|id| &self.frames[*id])
+            .ok_or_else(|| Error::MissingSprite(name.to_string()))
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Sprite, Error> {
+        self.frame(name).map(|frame| &frame.sprite)
+    }
+
+    pub fn tag(&self, name: &str) -> Result<&FrameTag, Error> {
+        self.tags
+            .get(name)
+            .ok_or_else(|| Error::MissingSprite(name.to_string()))
+    }
+
+    /// Frames of the tag, in the order given by its direction.
+    pub fn tag_frames(&self, name: &str) -> Result<Vec<&AtlasFrame>, Error> {
+        let tag = self.tag(name)?;
+        let forward = self.frames[tag.from..=tag.to].iter();
+        Ok(match tag.direction {
+            TagDirection::Forward => forward.collect(),
+            TagDirection::Reverse => forward.rev().collect(),
+            TagDirection::PingPong => {
+                let backward = self.frames[tag.from..tag.to].iter().skip(1).rev();
+                forward.chain(backward).collect()
+            }
+        })
+    }
+
+    fn from_json(sprite: &Sprite, atlas_json: AtlasJson) -> Result<Atlas, Error> {
+        let mut atlas = Atlas::default();
+        for (name, frame_json) in parse_frames(atlas_json.frames)? {
+            atlas.frame_ids.insert(name.clone(), atlas.frames.len());
+            atlas.frames.push(frame_json.into_frame(sprite, name)?);
+        }
+
+        for tag_json in atlas_json.meta.frame_tags {
+            if tag_json.from > tag_json.to || tag_json.to >= atlas.frames.len() {
+                return Err(Error::InvalidAtlas(format!(
+                    "tag {} refers to missing frames",
+                    tag_json.name
+                )));
+            }
+            let direction = match tag_json.direction.as_str() {
+                "" | "forward" => TagDirection::Forward,
+                "reverse" => TagDirection::Reverse,
+                "pingpong" => TagDirection::PingPong,
+                other => {
+                    return Err(Error::InvalidAtlas(format!(
+                        "unknown tag direction {}",
+                        other
+                    )))
+                }
+            };
+            let tag = FrameTag {
+                name: tag_json.name.clone(),
+                from: tag_json.from,
+                to: tag_json.to,
+                direction,
+            };
+            atlas.tags.insert(tag_json.name, tag);
+        }
+
+        Ok(atlas)
+    }
+}
+
+fn parse_json(json: &str) -> Result<AtlasJson, Error> {
+    serde_json::from_str(json).map_err(|err| Error::InvalidAtlas(err.to_string()))
+}
+
+fn parse_frames(frames: serde_json::Value) -> Result<Vec<(String, FrameJson)>, Error> {
+    match frames {
+        serde_json::Value::Object(frames) => frames
+            .into_iter()
+            .map(|(name, frame)| Ok((name, serde_json::from_value(frame)?)))
+            .collect::<Result<_, serde_json::Error>>(),
+        serde_json::Value::Array(frames) => frames
+            .into_iter()
+            .map(|frame| {
+                let frame: NamedFrameJson = serde_json::from_value(frame)?;
+                Ok((frame.filename, frame.frame))
+            })
+            .collect::<Result<_, serde_json::Error>>(),
+        _ => return Err(Error::InvalidAtlas("frames are not a map or a list".into())),
+    }
+    .map_err(|err| Error::InvalidAtlas(err.to_string()))
+}
+
+#[derive(Debug, Deserialize)]
+struct AtlasJson {
+    frames: serde_json::Value,
+    meta: MetaJson,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MetaJson {
+    image: String,
+    #[serde(default)]
+    frame_tags: Vec<TagJson>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagJson {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct NamedFrameJson {
+    filename: String,
+    #[serde(flatten)]
+    frame: FrameJson,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FrameJson {
+    frame: RectJson,
+    #[serde(default)]
+    rotated: bool,
+    #[serde(default)]
+    trimmed: bool,
+    sprite_source_size: Option<RectJson>,
+    source_size: Option<SizeJson>,
+    pivot: Option<PointJson>,
+    duration: Option<u32>,
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+struct RectJson {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+struct SizeJson {
+    w: u32,
+    h: u32,
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+struct PointJson {
+    x: f64,
+    y: f64,
+}
+
+/// Region of a frame in the atlas image, with the pivot of its sprite.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct FramePlacement {
+    offset: Vector2<u32>,
+    size: Vector2<u32>,
+    pivot: Vector2<i32>,
+}
+
+impl FrameJson {
+    fn into_frame(self, sprite: &Sprite, name: String) -> Result<AtlasFrame, Error> {
+        let placement = self.placement();
+        let source_size = self.source_size();
+        Ok(AtlasFrame {
+            name,
+            sprite: sprite
+                .slice(placement.size, placement.offset)?
+                .with_pivot(placement.pivot),
+            rotated: self.rotated,
+            trimmed: self.trimmed,
+            source_size: (source_size.w, source_size.h).into(),
+            duration_ms: self.duration,
+        })
+    }
+
+    fn source_size(&self) -> SizeJson {
+        self.source_size.unwrap_or(SizeJson {
+            w: self.frame.w,
+            h: self.frame.h,
+        })
+    }
+
+    fn placement(&self) -> FramePlacement {
+        let frame = self.frame;
+        let trimmed_rect = self.sprite_source_size.unwrap_or(RectJson {
+            x: 0,
+            y: 0,
+            ..frame
+        });
+        let source_size = self.source_size();
+
+        // both formats count from the top-left corner, while pivots are measured
+        // from the bottom-left one
+        let trim_offset = Vector2::new(
+            trimmed_rect.x as i32,
+            source_size.h as i32 - (trimmed_rect.y + trimmed_rect.h) as i32,
+        );
+        let source_pivot = self.pivot.map_or((0, 0).into(), |pivot| {
+            Vector2::new(
+                (pivot.x * source_size.w as f64).round() as i32,
+                ((1.0 - pivot.y) * source_size.h as f64).round() as i32,
+            )
+        });
+
+        let pivot = source_pivot - trim_offset;
+
+        // sizes are given upright, while rotated frames are stored turned clockwise,
+        // which moves the bottom-left corner to the top-left one
+        if self.rotated {
+            FramePlacement {
+                offset: (frame.x, frame.y).into(),
+                size: (frame.h, frame.w).into(),
+                pivot: (pivot.y, frame.w as i32 - pivot.x).into(),
+            }
+        } else {
+            FramePlacement {
+                offset: (frame.x, frame.y).into(),
+                size: (frame.w, frame.h).into(),
+                pivot,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIMMED_FRAME: &str = r#"{
+        "frame": {"x": 10, "y": 20, "w": 6, "h": 4},
+        "trimmed": true,
+        "spriteSourceSize": {"x": 3, "y": 2, "w": 6, "h": 4},
+        "sourceSize": {"w": 16, "h": 8},
+        "pivot": {"x": 0.5, "y": 1.0}
+    }"#;
+
+    fn parse_frame(frames: &str) -> (String, FrameJson) {
+        let json = format!(
+            r#"{{"frames": {}, "meta": {{"image": "atlas.png"}}}}"#,
+            frames
+        );
+        parse_frames(parse_json(&json).unwrap().frames)
+            .unwrap()
+            .remove(0)
+    }
+
+    #[test]
+    fn places_trimmed_frame() {
+        let (name, frame) = parse_frame(&format!(r#"{{"walk": {}}}"#, TRIMMED_FRAME));
+        assert_eq!(name, "walk");
+        assert_eq!(
+            frame.placement(),
+            FramePlacement {
+                offset: (10, 20).into(),
+                size: (6, 4).into(),
+                // pivot at the bottom middle of the 16x8 source, trimmed 3 from the left
+                // and 2 from the bottom
+                pivot: (5, -2).into(),
+            }
+        );
+    }
+
+    #[test]
+    fn parses_array_frames() {
+        let frames = format!(
+            r#"[{}]"#,
+            TRIMMED_FRAME.replacen('{', r#"{"filename": "walk", "#, 1)
+        );
+        let (name, frame) = parse_frame(&frames);
+        assert_eq!(name, "walk");
+        assert_eq!(frame.placement().size, (6, 4).into());
+    }
+
+    #[test]
+    fn places_rotated_trimmed_frame() {
+        let rotated = r#"{"walk": {
+            "frame": {"x": 10, "y": 20, "w": 6, "h": 4},
+            "rotated": true,
+            "trimmed": true,
+            "spriteSourceSize": {"x": 3, "y": 2, "w": 6, "h": 4},
+            "sourceSize": {"w": 16, "h": 8},
+            "pivot": {"x": 0.5, "y": 1.0}
+        }}"#;
+        let (_, frame) = parse_frame(rotated);
+        assert_eq!(
+            frame.placement(),
+            FramePlacement {
+                offset: (10, 20).into(),
+                size: (4, 6).into(),
+                // the upright pivot (5, -2) turned clockwise with the frame
+                pivot: (-2, 1).into(),
+            }
+        );
+    }
+}
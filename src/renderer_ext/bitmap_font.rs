@@ -1,10 +1,18 @@
-use crate::renderer::color;
 use crate::renderer::color::Color;
-use crate::renderer_ext::bitmap_font::MissingCharacterBehaviour::{Panic, Skip};
-use crate::renderer_ext::bitmap_font::SpaceBehaviour::TreatAsCharacter;
+use crate::renderer::distance_field::DistanceFieldEffects;
+use crate::renderer::Error;
+use crate::renderer_ext::bmfont::BmFont;
+use crate::renderer_ext::text_box::TextBox;
+use crate::renderer_ext::text_layout::{
+    graphemes, LineRange, PositionedGlyph, TextBounds, TextLayout, TextLine,
+};
 use crate::{Layer, RenderContext, Renderer, Sprite};
 use cgmath::Vector2;
-use std::collections::HashMap;
+use image::RgbaImage;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
 
 #[derive(Debug, Clone, Default)]
 pub enum SpaceBehaviour {
@@ -14,11 +22,15 @@ pub enum SpaceBehaviour {
     DrawOtherCharacter(char),
 }
 
+/// What is drawn for characters missing from the font and all its fallbacks.
 #[derive(Debug, Clone, Default)]
 pub enum MissingCharacterBehaviour {
+    /// Skipped, with a warning logged the first time each character is met.
     #[default]
+    Warn,
     Panic,
     Skip,
+    /// Drawn as the other character, or handled like `Warn` when it is missing too.
     DrawOtherCharacter(char),
 }
 
@@ -28,21 +40,165 @@ pub struct BitmapFontSettings {
     missing_character_behaviour: MissingCharacterBehaviour,
 }
 
+#[allow(dead_code)]
+impl BitmapFontSettings {
+    pub fn with_space_behaviour(self, space_behaviour: SpaceBehaviour) -> BitmapFontSettings {
+        BitmapFontSettings {
+            space_behaviour,
+            ..self
+        }
+    }
+
+    pub fn with_missing_character_behaviour(
+        self,
+        missing_character_behaviour: MissingCharacterBehaviour,
+    ) -> BitmapFontSettings {
+        BitmapFontSettings {
+            missing_character_behaviour,
+            ..self
+        }
+    }
+}
+
+/// How `BitmapFont::proportional` fits glyphs to their pixels.
+#[derive(Debug, Copy, Clone)]
+pub struct ProportionalSettings {
+    /// Pixels with alpha above the threshold are treated as opaque.
+    pub alpha_threshold: u8,
+    /// Empty columns added after every trimmed glyph.
+    pub spacing: i32,
+}
+
+impl Default for ProportionalSettings {
+    fn default() -> Self {
+        ProportionalSettings {
+            alpha_threshold: 0,
+            spacing: 1,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub enum TextAlignment {
     #[default]
     Left,
     Center,
     Right,
+    /// Wrapped lines stretched to the width of their text box; elsewhere the same as `Left`.
+    Justified,
+}
+
+/// Part of the text placed at the drawing position.
+#[allow(dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum TextAnchor {
+    Top,
+    Middle,
+    /// Base line of the first line.
+    Baseline,
+    /// Bottom of the last line.
+    #[default]
+    Bottom,
+}
+
+/// How text is drawn. Distance field fonts stay crisp at any scale and can have effects,
+/// which other fonts ignore.
+#[derive(Debug, Clone)]
+pub struct TextStyle {
+    pub alignment: TextAlignment,
+    /// Ignored by text boxes, which place lines by their own vertical alignment.
+    pub anchor: TextAnchor,
+    /// Space added on both sides of glyphs and lines, in pixels of the font.
+    pub padding: Vector2<i32>,
+    pub color: Color,
+    pub scale: f32,
+    pub effects: DistanceFieldEffects,
+}
+
+impl TextStyle {
+    pub fn new(color: Color) -> TextStyle {
+        TextStyle {
+            alignment: TextAlignment::default(),
+            anchor: TextAnchor::default(),
+            padding: (0, 0).into(),
+            color,
+            scale: 1.0,
+            effects: DistanceFieldEffects::default(),
+        }
+    }
+
+    pub fn with_alignment(self, alignment: TextAlignment) -> TextStyle {
+        TextStyle { alignment, ..self }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_anchor(self, anchor: TextAnchor) -> TextStyle {
+        TextStyle { anchor, ..self }
+    }
+
+    pub fn with_padding(self, padding: Vector2<i32>) -> TextStyle {
+        TextStyle { padding, ..self }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_scale(self, scale: f32) -> TextStyle {
+        TextStyle { scale, ..self }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_effects(self, effects: DistanceFieldEffects) -> TextStyle {
+        TextStyle { effects, ..self }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Glyph {
+    /// Drawn with its pivot at the pen position; `None` for glyphs without pixels.
+    pub sprite: Option<Sprite>,
+    /// Distance the pen moves after drawing the glyph.
+    pub advance: i32,
+}
+
+/// Vertical metrics of a font, in its pixels. Glyphs are drawn with the drawing position
+/// at the bottom of their line.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct FontMetrics {
+    /// Distance between bottoms of consecutive lines, without padding.
+    pub line_height: i32,
+    /// Distance from the base line up to the top of the tallest glyphs.
+    pub ascent: i32,
+    /// Distance from the bottom of a line up to its base line.
+    pub baseline: i32,
+}
+
+/// Glyph a character is drawn with, possibly taken from a fallback font.
+struct ResolvedGlyph<'a> {
+    sprite: Option<&'a Sprite>,
+    advance: i32,
+    /// Moves glyphs of fallback fonts onto the base line of this font.
+    rise: i32,
 }
 
 #[derive(Debug, Clone)]
 pub struct BitmapFont {
-    characters_map: HashMap<char, Sprite>,
+    characters_map: HashMap<char, Glyph>,
+    /// Extra advance between pairs of characters.
+    kerning: HashMap<(char, char), i32>,
+    metrics: FontMetrics,
     settings: BitmapFontSettings,
+    /// Searched in order for characters missing from this font.
+    fallbacks: Vec<BitmapFont>,
+    /// Characters already reported as missing, so each one is logged once. Kept in
+    /// a `RefCell`, as text is laid out by shared reference.
+    reported_characters: RefCell<HashSet<char>>,
+    /// Set for fonts generated on demand: characters missing during layout, which the
+    /// generating font adds before the next frame.
+    requested_characters: Option<RefCell<HashSet<char>>>,
 }
 
 impl BitmapFont {
+    /// Font of glyphs standing on the bottom of their line, as tall as the tallest sprite.
+    /// Other metrics can be set with `with_metrics`.
     pub fn new(
         sprites: &[Sprite],
         characters_list: impl Iterator<Item = char>,
@@ -50,93 +206,455 @@ impl BitmapFont {
     ) -> BitmapFont {
         let mut characters_map = HashMap::new();
         for (character, sprite) in characters_list.zip(sprites.iter()) {
-            characters_map.insert(character, sprite.clone());
+            let glyph = Glyph {
+                sprite: Some(sprite.clone()),
+                advance: sprite.get_size().x as i32,
+            };
+            characters_map.insert(character, glyph);
         }
+        let line_height = sprites
+            .iter()
+            .map(|sprite| sprite.get_size().y as i32)
+            .max()
+            .unwrap_or(0);
         BitmapFont {
             characters_map,
+            kerning: HashMap::new(),
+            metrics: FontMetrics {
+                line_height,
+                ascent: line_height,
+                baseline: 0,
+            },
+            settings,
+            fallbacks: vec![],
+            reported_characters: RefCell::default(),
+            requested_characters: None,
+        }
+    }
+
+    /// Like `new`, with every glyph trimmed to its opaque columns and advancing by its
+    /// trimmed width. Glyphs without opaque pixels, like spaces, keep the width of their
+    /// cell. Sprites have to keep their pixels on the CPU.
+    #[allow(dead_code)]
+    pub fn proportional(
+        sprites: &[Sprite],
+        characters_list: impl Iterator<Item = char>,
+        settings: BitmapFontSettings,
+        proportional_settings: ProportionalSettings,
+    ) -> Result<BitmapFont, Error> {
+        let mut font = BitmapFont::new(sprites, std::iter::empty(), settings);
+        for (character, sprite) in characters_list.zip(sprites.iter()) {
+            let pixels = sprite.get_pixels().ok_or(Error::MissingPixelData)?;
+            let glyph = match opaque_columns(&pixels, proportional_settings.alpha_threshold) {
+                Some((first, last)) => {
+                    let size = (last - first + 1, sprite.get_size().y).into();
+                    Glyph {
+                        sprite: Some(sprite.slice(size, (first, 0).into())?),
+                        advance: size.x as i32 + proportional_settings.spacing,
+                    }
+                }
+                None => Glyph {
+                    sprite: None,
+                    advance: sprite.get_size().x as i32,
+                },
+            };
+            font.insert_glyph(character, glyph);
+        }
+        Ok(font)
+    }
+
+    #[allow(dead_code)]
+    pub fn with_metrics(self, metrics: FontMetrics) -> BitmapFont {
+        BitmapFont { metrics, ..self }
+    }
+
+    /// Adds a font drawing characters missing from this one, after previously added ones.
+    /// Its glyphs are moved onto the base line of this font. Fonts generated on demand,
+    /// like `TrueTypeFont`, are added as they are: characters they have not rasterized
+    /// yet, e.g. with `cache_text`, stay missing.
+    #[allow(dead_code)]
+    pub fn with_fallback(mut self, font: BitmapFont) -> BitmapFont {
+        // nothing generates characters requested from the copy
+        self.fallbacks.push(BitmapFont {
+            requested_characters: None,
+            ..font
+        });
+        self
+    }
+
+    /// Replaces the glyph of the character, or adds it.
+    #[allow(dead_code)]
+    pub fn with_glyph(mut self, character: char, glyph: Glyph) -> BitmapFont {
+        self.insert_glyph(character, glyph);
+        self
+    }
+
+    /// Overrides the advance of the character's glyph; missing characters are ignored.
+    #[allow(dead_code)]
+    pub fn with_advance(mut self, character: char, advance: i32) -> BitmapFont {
+        if let Some(glyph) = self.characters_map.get_mut(&character) {
+            glyph.advance = advance;
+        }
+        self
+    }
+
+    /// Loads an AngelCode BMFont `.fnt` file, text or binary, together with its page images.
+    /// Pages of fonts declaring a distance field are drawn with the distance field shader.
+    #[allow(dead_code)]
+    pub fn load_bmfont(
+        renderer: &mut Renderer,
+        path: &str,
+        settings: BitmapFontSettings,
+    ) -> Result<BitmapFont, Error> {
+        let description = BmFont::parse(&fs::read(path)?)?;
+        let pages = description
+            .pages
+            .iter()
+            .map(|page| {
+                let page_path = Path::new(path).with_file_name(page);
+                let page_path = page_path.to_string_lossy();
+                match description.distance_field {
+                    Some(field) => renderer.load_distance_field_sprite(&page_path, field),
+                    None => renderer.load_sprite(&page_path),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_bmfont(&description, &pages, settings)
+    }
+
+    /// Builds a font from a BMFont description, with `pages` loaded in the description's order.
+    ///
+    /// Glyphs are positioned so that the drawing position is the bottom of the line.
+    #[allow(dead_code)]
+    pub fn from_bmfont(
+        description: &BmFont,
+        pages: &[Sprite],
+        settings: BitmapFontSettings,
+    ) -> Result<BitmapFont, Error> {
+        let line_height = description.line_height as i32;
+        let mut characters_map = HashMap::new();
+        for bm_char in description.chars.iter() {
+            let character = match char::from_u32(bm_char.id) {
+                Some(character) => character,
+                None => continue,
+            };
+            let sprite = if bm_char.width > 0 && bm_char.height > 0 {
+                let page = pages
+                    .get(bm_char.page)
+                    .ok_or_else(|| Error::InvalidFont(format!("missing page {}", bm_char.page)))?;
+                let sprite = page
+                    .slice(
+                        (bm_char.width, bm_char.height).into(),
+                        (bm_char.x, bm_char.y).into(),
+                    )?
+                    .with_pivot(bm_char.pivot(line_height));
+                Some(sprite)
+            } else {
+                None
+            };
+            let glyph = Glyph {
+                sprite,
+                advance: bm_char.x_advance,
+            };
+            characters_map.insert(character, glyph);
+        }
+
+        let kerning = description
+            .kernings
+            .iter()
+            .filter_map(|kerning| {
+                let first = char::from_u32(kerning.first)?;
+                let second = char::from_u32(kerning.second)?;
+                Some(((first, second), kerning.amount))
+            })
+            .collect();
+
+        Ok(BitmapFont {
+            characters_map,
+            kerning,
+            metrics: FontMetrics {
+                line_height,
+                ascent: description.base as i32,
+                baseline: line_height - description.base as i32,
+            },
+            settings,
+            fallbacks: vec![],
+            reported_characters: RefCell::default(),
+            requested_characters: None,
+        })
+    }
+
+    /// Font without glyphs, filled in later by generated fonts.
+    pub(in crate::renderer_ext) fn empty(
+        metrics: FontMetrics,
+        settings: BitmapFontSettings,
+    ) -> BitmapFont {
+        BitmapFont {
+            characters_map: HashMap::new(),
+            kerning: HashMap::new(),
+            metrics,
             settings,
+            fallbacks: vec![],
+            reported_characters: RefCell::default(),
+            requested_characters: Some(RefCell::default()),
         }
     }
 
-    fn get_real_char(&self, character: char) -> (Option<char>, i32) {
+    /// Characters missing during layout since the last call, for fonts generated on demand.
+    pub(in crate::renderer_ext) fn take_requested_characters(&self) -> Vec<char> {
+        self.requested_characters
+            .as_ref()
+            .map_or(vec![], |requested| requested.borrow_mut().drain().collect())
+    }
+
+    pub(in crate::renderer_ext) fn insert_glyph(&mut self, character: char, glyph: Glyph) {
+        self.characters_map.insert(character, glyph);
+    }
+
+    pub(in crate::renderer_ext) fn insert_kerning(
+        &mut self,
+        first: char,
+        second: char,
+        amount: i32,
+    ) {
+        self.kerning.insert((first, second), amount);
+    }
+
+    pub(in crate::renderer_ext) fn characters(&self) -> impl Iterator<Item = char> + '_ {
+        self.characters_map.keys().copied()
+    }
+
+    #[allow(dead_code)]
+    pub fn get_glyph(&self, character: char) -> Option<&Glyph> {
+        self.characters_map.get(&character)
+    }
+
+    pub fn get_metrics(&self) -> FontMetrics {
+        self.metrics
+    }
+
+    pub fn get_kerning(&self, first: char, second: char) -> i32 {
+        self.kerning.get(&(first, second)).copied().unwrap_or(0)
+    }
+
+    fn resolve_glyph(&self, character: char) -> ResolvedGlyph<'_> {
         // handle space behaviour
-        let (space_replaced_ch, space_skip_draw) = if character == ' ' {
-            match self.settings.space_behaviour {
-                SpaceBehaviour::TreatAsCharacter => (' ', None),
-                SpaceBehaviour::SizedEmptySpace(space) => (' ', Some(space)),
-                SpaceBehaviour::DrawOtherCharacter(ch) => (ch, None),
+        let character = match (character, &self.settings.space_behaviour) {
+            (' ', SpaceBehaviour::SizedEmptySpace(space)) => {
+                return ResolvedGlyph {
+                    sprite: None,
+                    advance: *space,
+                    rise: 0,
+                }
             }
-        } else {
-            (character, None)
+            (' ', SpaceBehaviour::DrawOtherCharacter(ch)) => *ch,
+            _ => character,
         };
-        if let Some(space) = space_skip_draw {
-            return (None, space);
-        }
-
-        let real_ch = if self.characters_map.contains_key(&space_replaced_ch) {
-            Some(space_replaced_ch)
-        } else {
-            match self.settings.missing_character_behaviour {
-                Panic => panic!("Missing character {}", space_replaced_ch),
-                Skip => None,
-                MissingCharacterBehaviour::DrawOtherCharacter(ch) => {
-                    if self.characters_map.contains_key(&ch) {
-                        Some(ch)
-                    } else {
-                        panic!("Missing replacement character {}", ch)
-                    }
+
+        if let Some(glyph) = self.find_glyph(character) {
+            return glyph;
+        }
+        let replacement = match self.settings.missing_character_behaviour {
+            MissingCharacterBehaviour::Warn => None,
+            // fonts generated on demand get the character before the next frame
+            MissingCharacterBehaviour::Panic if self.requested_characters.is_some() => None,
+            MissingCharacterBehaviour::Panic => panic!("Missing character {}", character),
+            MissingCharacterBehaviour::Skip => {
+                return ResolvedGlyph {
+                    sprite: None,
+                    advance: 0,
+                    rise: 0,
                 }
             }
+            MissingCharacterBehaviour::DrawOtherCharacter(ch) => self.find_glyph(ch),
         };
+        replacement.unwrap_or_else(|| {
+            // fonts generated on demand report characters they cannot generate themselves
+            if self.requested_characters.is_none()
+                && self.reported_characters.borrow_mut().insert(character)
+            {
+                log::warn!(
+                    "Missing character {:?} ({:#x})",
+                    character,
+                    character as u32
+                );
+            }
+            ResolvedGlyph {
+                sprite: None,
+                advance: 0,
+                rise: 0,
+            }
+        })
+    }
 
-        if let Some(ch) = real_ch {
-            let size = self.characters_map[&ch].get_size().x as i32;
-            (Some(ch), size)
-        } else {
-            (None, 0)
+    /// Glyph of the character from this font or the first fallback which has it.
+    fn find_glyph(&self, character: char) -> Option<ResolvedGlyph<'_>> {
+        if let Some(glyph) = self.characters_map.get(&character) {
+            return Some(ResolvedGlyph {
+                sprite: glyph.sprite.as_ref(),
+                advance: glyph.advance,
+                rise: 0,
+            });
+        }
+        if let Some(requested) = &self.requested_characters {
+            requested.borrow_mut().insert(character);
         }
+        self.fallbacks.iter().find_map(|fallback| {
+            let glyph = fallback.find_glyph(character)?;
+            Some(ResolvedGlyph {
+                rise: glyph.rise + self.metrics.baseline - fallback.metrics.baseline,
+                ..glyph
+            })
+        })
     }
 
-    fn draw_char(
+    /// Places every glyph of the text, as it would be drawn at `position`, which is placed
+    /// on the text as set by the style's anchor.
+    pub fn layout(&self, text: &str, position: Vector2<i32>, style: &TextStyle) -> TextLayout {
+        let lines = LineRange::split_lines(text);
+        let line_height = self.metrics.line_height as f32 * style.scale;
+        let block_height = self.block_height(lines.len(), style);
+        let first_line_y = position.y as f32
+            + match style.anchor {
+                TextAnchor::Top => -line_height,
+                TextAnchor::Middle => block_height / 2.0 - line_height,
+                TextAnchor::Baseline => -(self.metrics.baseline as f32 * style.scale),
+                TextAnchor::Bottom => block_height - line_height,
+            };
+        let first_line_position = (position.x, first_line_y.round() as i32).into();
+        self.layout_lines(text, &lines, first_line_position, style, None)
+    }
+
+    /// Height of lines laid out one under another, from the top of the first one
+    /// to the bottom of the last one.
+    pub(in crate::renderer_ext) fn block_height(
         &self,
-        ctx: &mut RenderContext,
-        character: char,
-        position: Vector2<i32>,
-        layer: Layer,
-        color: Color,
-    ) -> i32 {
-        let (real_ch, space) = self.get_real_char(character);
-        if let Some(ch) = real_ch {
-            let sprite = &self.characters_map[&ch];
-            ctx.draw_sprite(sprite, position, layer, color);
+        line_count: usize,
+        style: &TextStyle,
+    ) -> f32 {
+        let line_height = self.metrics.line_height as f32;
+        let line_gap = (style.padding.y * 2) as f32;
+        match line_count {
+            0 => 0.0,
+            count => (count as f32 * line_height + (count - 1) as f32 * line_gap) * style.scale,
         }
-        space
     }
 
-    fn draw_line(
+    /// Places every glyph of the text inside the box, wrapping lines at spaces, or inside
+    /// words longer than the box is wide.
+    #[allow(dead_code)]
+    pub fn layout_in_box(&self, text: &str, text_box: &TextBox, style: &TextStyle) -> TextLayout {
+        text_box.layout(self, text, style)
+    }
+
+    /// Lays out lines of the text one under another, with the first one at `position`.
+    /// Wrapped lines of justified text are stretched to `justified_width`.
+    pub(in crate::renderer_ext) fn layout_lines(
         &self,
-        ctx: &mut RenderContext,
-        line: &str,
+        text: &str,
+        lines: &[LineRange],
         position: Vector2<i32>,
-        layer: Layer,
-        alignment: &TextAlignment,
-        padding: i32,
-        color: Color,
-    ) {
-        use TextAlignment::*;
-        let string_size: i32 = line.chars().map(|ch| self.get_real_char(ch).1).sum();
-        let x_position = position.x
-            - match alignment {
-                Left => 0,
-                Center => string_size / 2,
-                Right => string_size,
+        style: &TextStyle,
+        justified_width: Option<f32>,
+    ) -> TextLayout {
+        let scale = |value: i32| value as f32 * style.scale;
+        let line_height = self.metrics.line_height;
+        let scaled_line_height = scale(line_height).round() as i32;
+
+        let mut glyphs = vec![];
+        let mut text_lines = vec![];
+        let mut current_y = position.y as f32;
+        for (line_id, line) in lines.iter().enumerate() {
+            let line_text = line.text(text);
+            let line_y = current_y.round() as i32;
+
+            let string_size = scale(self.line_width(&line_text, style.padding.x));
+            let x_position = position.x as f32
+                - match style.alignment {
+                    TextAlignment::Left | TextAlignment::Justified => 0.0,
+                    TextAlignment::Center => (string_size / 2.0).floor(),
+                    TextAlignment::Right => string_size,
+                };
+            let space_stretch = match (&style.alignment, justified_width) {
+                (TextAlignment::Justified, Some(width)) if line.wrapped => {
+                    let spaces = graphemes(&line_text)
+                        .filter(|(_, _, ch)| *ch == ' ')
+                        .count();
+                    match spaces {
+                        0 => 0.0,
+                        _ => (width - string_size).max(0.0) / spaces as f32,
+                    }
+                }
+                _ => 0.0,
             };
-        let mut current_position = (x_position, position.y).into();
 
-        for ch in line.chars() {
-            let offset = self.draw_char(ctx, ch, current_position, layer, color);
-            current_position.x += offset + padding * 2;
+            // kept unrounded, so scaled glyphs do not drift apart
+            let mut current_x = x_position;
+            let mut previous = None;
+            for (byte_index, grapheme, ch) in graphemes(&line_text) {
+                if let Some(previous) = previous {
+                    current_x += scale(self.get_kerning(previous, ch));
+                }
+                let glyph = self.resolve_glyph(ch);
+                let pen = Vector2::new(current_x.round() as i32, line_y);
+                current_x += scale(glyph.advance + style.padding.x * 2);
+                if ch == ' ' {
+                    current_x += space_stretch;
+                }
+                // graphemes of the suffix point at the end of the line
+                let byte_index = line.start + byte_index;
+                let byte_len = match byte_index < line.end {
+                    true => grapheme.len(),
+                    false => 0,
+                };
+                glyphs.push(PositionedGlyph {
+                    byte_index: byte_index.min(line.end),
+                    byte_len,
+                    character: ch,
+                    line: line_id,
+                    position: pen + Vector2::new(0, scale(glyph.rise).round() as i32),
+                    bounds: TextBounds {
+                        origin: pen,
+                        size: (current_x.round() as i32 - pen.x, scaled_line_height).into(),
+                    },
+                    sprite: glyph.sprite.cloned(),
+                });
+                previous = Some(ch);
+            }
+
+            let line_width = (current_x - x_position - scale(style.padding.x * 2)).max(0.0);
+            text_lines.push(TextLine {
+                bounds: TextBounds {
+                    origin: (x_position.round() as i32, line_y).into(),
+                    size: (line_width.round() as i32, scaled_line_height).into(),
+                },
+                start: line.start,
+                end: line.end,
+            });
+            current_y -= scale(line_height + style.padding.y * 2);
         }
+        TextLayout::new(glyphs, text_lines)
+    }
+
+    /// Size of the bounding box of the text, multiple lines included.
+    #[allow(dead_code)]
+    pub fn measure(&self, text: &str, style: &TextStyle) -> Vector2<i32> {
+        self.layout(text, (0, 0).into(), style).size()
+    }
+
+    /// Width of a line from its first glyph to the end of its last one.
+    pub(in crate::renderer_ext) fn line_width(&self, line: &str, padding: i32) -> i32 {
+        let characters: Vec<char> = graphemes(line).map(|(_, _, ch)| ch).collect();
+        let advances: i32 = characters
+            .iter()
+            .map(|ch| self.resolve_glyph(*ch).advance)
+            .sum();
+        let kerning: i32 = characters
+            .windows(2)
+            .map(|pair| self.get_kerning(pair[0], pair[1]))
+            .sum();
+        let gaps = characters.len().saturating_sub(1) as i32 * padding * 2;
+        advances + kerning + gaps
     }
 
     pub(in crate::renderer_ext) fn draw_text(
@@ -145,28 +663,192 @@ impl BitmapFont {
         text: &str,
         position: Vector2<i32>,
         layer: Layer,
-        alignment: &TextAlignment,
-        padding: Vector2<i32>,
-        color: Color,
+        style: &TextStyle,
     ) {
-        let mut current_position = position;
-        let lines = text.lines();
-        let line_height = text
-            .chars()
-            .next()
-            .and_then(|ch| self.characters_map.get(&ch))
-            .map_or(0, |ch| ch.get_size().y) as i32;
-        for line in lines {
-            self.draw_line(
-                ctx,
-                line,
-                current_position,
-                layer,
-                alignment,
-                padding.x,
-                color,
-            );
-            current_position.y -= line_height + padding.y * 2;
+        self.draw_layout(ctx, &self.layout(text, position, style), layer, style);
+    }
+
+    pub(in crate::renderer_ext) fn draw_layout(
+        &self,
+        ctx: &mut RenderContext,
+        layout: &TextLayout,
+        layer: Layer,
+        style: &TextStyle,
+    ) {
+        for glyph in layout.glyphs() {
+            if let Some(sprite) = &glyph.sprite {
+                ctx.draw_sprite_with_effects(
+                    sprite,
+                    glyph.position,
+                    layer,
+                    style.color,
+                    style.scale,
+                    &style.effects,
+                );
+            }
         }
     }
 }
+
+/// First and last columns of the image with a pixel more opaque than the threshold.
+fn opaque_columns(pixels: &RgbaImage, alpha_threshold: u8) -> Option<(u32, u32)> {
+    let is_opaque =
+        |x: u32| (0..pixels.height()).any(|y| pixels.get_pixel(x, y)[3] > alpha_threshold);
+    let first = (0..pixels.width()).find(|x| is_opaque(*x))?;
+    let last = (first..pixels.width()).rev().find(|x| is_opaque(*x))?;
+    Some((first, last))
+}
+
+#[cfg(test)]
+pub(in crate::renderer_ext) mod tests {
+    use super::*;
+    use crate::renderer::color::WHITE;
+
+    /// Font of glyphs without sprites, which needs no renderer.
+    pub(in crate::renderer_ext) fn sprite_less_font(
+        glyphs: &[(char, i32)],
+        metrics: FontMetrics,
+        settings: BitmapFontSettings,
+    ) -> BitmapFont {
+        glyphs.iter().fold(
+            BitmapFont::new(&[], std::iter::empty(), settings).with_metrics(metrics),
+            |font, (character, advance)| {
+                let glyph = Glyph {
+                    sprite: None,
+                    advance: *advance,
+                };
+                font.with_glyph(*character, glyph)
+            },
+        )
+    }
+
+    fn font(
+        glyphs: &[(char, i32)],
+        baseline: i32,
+        behaviour: MissingCharacterBehaviour,
+    ) -> BitmapFont {
+        let settings = BitmapFontSettings::default().with_missing_character_behaviour(behaviour);
+        let metrics = FontMetrics {
+            line_height: 10,
+            ascent: 8,
+            baseline,
+        };
+        sprite_less_font(glyphs, metrics, settings)
+    }
+
+    fn resolve(font: &BitmapFont, character: char) -> (i32, i32) {
+        let glyph = font.resolve_glyph(character);
+        (glyph.advance, glyph.rise)
+    }
+
+    #[test]
+    fn searches_fallbacks_in_order() {
+        let main = font(&[('a', 1)], 2, MissingCharacterBehaviour::Warn)
+            .with_fallback(font(&[('b', 2)], 3, MissingCharacterBehaviour::Warn))
+            .with_fallback(font(
+                &[('b', 3), ('c', 4)],
+                5,
+                MissingCharacterBehaviour::Warn,
+            ));
+        assert_eq!(resolve(&main, 'a'), (1, 0));
+        // fallback glyphs are moved by the difference of base lines
+        assert_eq!(resolve(&main, 'b'), (2, -1));
+        assert_eq!(resolve(&main, 'c'), (4, -3));
+    }
+
+    #[test]
+    fn rises_through_nested_fallbacks() {
+        let nested = font(&[('a', 1)], 6, MissingCharacterBehaviour::Warn);
+        let main = font(&[], 1, MissingCharacterBehaviour::Warn)
+            .with_fallback(font(&[], 4, MissingCharacterBehaviour::Warn).with_fallback(nested));
+        assert_eq!(resolve(&main, 'a'), (1, -5));
+    }
+
+    #[test]
+    fn reports_missing_characters_once() {
+        let main = font(&[('a', 1)], 0, MissingCharacterBehaviour::Warn);
+        assert_eq!(resolve(&main, 'x'), (0, 0));
+        assert_eq!(resolve(&main, 'x'), (0, 0));
+        assert_eq!(
+            main.reported_characters.borrow().iter().collect::<Vec<_>>(),
+            vec![&'x']
+        );
+    }
+
+    #[test]
+    fn skips_or_replaces_missing_characters() {
+        let skipping = font(&[('a', 1)], 0, MissingCharacterBehaviour::Skip);
+        assert_eq!(resolve(&skipping, 'x'), (0, 0));
+        assert!(skipping.reported_characters.borrow().is_empty());
+
+        let replacing = font(
+            &[('?', 3)],
+            0,
+            MissingCharacterBehaviour::DrawOtherCharacter('?'),
+        );
+        assert_eq!(resolve(&replacing, 'x'), (3, 0));
+        assert!(replacing.reported_characters.borrow().is_empty());
+    }
+
+    #[test]
+    fn requests_characters_of_fonts_generated_on_demand() {
+        let metrics = FontMetrics::default();
+        let on_demand = BitmapFont::empty(metrics, BitmapFontSettings::default());
+        assert_eq!(resolve(&on_demand, 'x'), (0, 0));
+        assert_eq!(on_demand.take_requested_characters(), vec!['x']);
+        assert!(on_demand.take_requested_characters().is_empty());
+        assert!(on_demand.reported_characters.borrow().is_empty());
+    }
+
+    #[test]
+    fn places_text_by_anchor() {
+        let font = font(&[('a', 4)], 2, MissingCharacterBehaviour::Warn);
+        let line_bottoms = |anchor| {
+            let style = TextStyle::new(WHITE).with_anchor(anchor);
+            let layout = font.layout("a\na", (0, 100).into(), &style);
+            let bottoms: Vec<i32> = layout
+                .lines()
+                .iter()
+                .map(|line| line.bounds.origin.y)
+                .collect();
+            bottoms
+        };
+        assert_eq!(line_bottoms(TextAnchor::Top), vec![90, 80]);
+        assert_eq!(line_bottoms(TextAnchor::Middle), vec![100, 90]);
+        assert_eq!(line_bottoms(TextAnchor::Baseline), vec![98, 88]);
+        assert_eq!(line_bottoms(TextAnchor::Bottom), vec![110, 100]);
+        assert_eq!(TextStyle::new(WHITE).anchor, TextAnchor::Bottom);
+    }
+
+    #[test]
+    #[should_panic(expected = "Missing character x")]
+    fn panics_on_missing_characters_when_set() {
+        let font = font(&[('a', 1)], 0, MissingCharacterBehaviour::Panic);
+        font.resolve_glyph('x');
+    }
+
+    #[test]
+    fn resolves_spaces_by_space_behaviour() {
+        let space_font = |behaviour| {
+            let settings = BitmapFontSettings::default().with_space_behaviour(behaviour);
+            sprite_less_font(&[(' ', 3), ('a', 1)], FontMetrics::default(), settings)
+        };
+        let sized = space_font(SpaceBehaviour::SizedEmptySpace(7));
+        assert_eq!(resolve(&sized, ' '), (7, 0));
+        let other = space_font(SpaceBehaviour::DrawOtherCharacter('a'));
+        assert_eq!(resolve(&other, ' '), (1, 0));
+        assert_eq!(
+            resolve(&space_font(SpaceBehaviour::TreatAsCharacter), ' '),
+            (3, 0)
+        );
+    }
+
+    #[test]
+    fn stops_requesting_characters_of_fallback_copies() {
+        let on_demand = BitmapFont::empty(FontMetrics::default(), BitmapFontSettings::default());
+        let main = font(&[], 0, MissingCharacterBehaviour::Warn).with_fallback(on_demand);
+        assert_eq!(resolve(&main, 'x'), (0, 0));
+        assert!(main.fallbacks[0].take_requested_characters().is_empty());
+        assert!(main.reported_characters.borrow().contains(&'x'));
+    }
+}
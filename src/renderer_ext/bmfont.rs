@@ -0,0 +1,427 @@
+use crate::renderer::distance_field::{DistanceField, DistanceFieldKind};
+use crate::renderer::Error;
+use cgmath::Vector2;
+use std::collections::HashMap;
+
+const BINARY_MAGIC: &[u8] = b"BMF";
+const BINARY_VERSION: u8 = 3;
+const BINARY_CHAR_SIZE: usize = 20;
+const BINARY_KERNING_SIZE: usize = 10;
+
+/// Description of an AngelCode BMFont, read from its `.fnt` file.
+///
+/// Both the text and the binary (version 3) variants are supported; XML is not.
+/// Glyph positions are in pixels relative to the top-left corner of their page.
+#[derive(Debug, Clone, Default)]
+pub struct BmFont {
+    pub line_height: u32,
+    /// Distance from the top of a line to the base line of its glyphs.
+    pub base: u32,
+    /// Image file names, relative to the `.fnt` file.
+    pub pages: Vec<String>,
+    pub chars: Vec<BmChar>,
+    pub kernings: Vec<BmKerning>,
+    /// Set for SDF and MSDF fonts, as declared by the `distanceField` line of text fonts.
+    pub distance_field: Option<DistanceField>,
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct BmChar {
+    pub id: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: i32,
+    /// Distance from the top of the line to the top of the glyph.
+    pub y_offset: i32,
+    pub x_advance: i32,
+    pub page: usize,
+}
+
+impl BmChar {
+    /// Pivot of the glyph's sprite, which puts the glyph in place when it is drawn
+    /// at the bottom of a line of the given height.
+    pub fn pivot(&self, line_height: i32) -> Vector2<i32> {
+        let glyph_bottom = line_height - self.y_offset - self.height as i32;
+        (-self.x_offset, -glyph_bottom).into()
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct BmKerning {
+    pub first: u32,
+    pub second: u32,
+    pub amount: i32,
+}
+
+impl BmFont {
+    pub fn parse(data: &[u8]) -> Result<BmFont, Error> {
+        if data.starts_with(BINARY_MAGIC) {
+            Self::parse_binary(data)
+        } else {
+            let text = std::str::from_utf8(data)
+                .map_err(|_| Error::InvalidFont("text font is not valid UTF-8".to_string()))?;
+            Self::parse_text(text)
+        }
+    }
+
+    pub fn parse_text(text: &str) -> Result<BmFont, Error> {
+        let mut font = BmFont::default();
+        for (line_id, line) in text.lines().enumerate() {
+            let mut tokens = tokenize(line);
+            let tag = match tokens.next() {
+                Some(tag) => tag,
+                None => continue,
+            };
+            let attributes = Attributes {
+                values: tokens.filter_map(|token| token.split_once('=')).collect(),
+                line: line_id + 1,
+            };
+
+            match tag {
+                "common" => {
+                    font.line_height = attributes.get("lineHeight")?;
+                    font.base = attributes.get("base")?;
+                }
+                "page" => {
+                    let id: usize = attributes.get("id")?;
+                    if font.pages.len() <= id {
+                        font.pages.resize(id + 1, String::new());
+                    }
+                    font.pages[id] = attributes.get_string("file")?;
+                }
+                "char" => font.chars.push(BmChar {
+                    id: attributes.get("id")?,
+                    x: attributes.get("x")?,
+                    y: attributes.get("y")?,
+                    width: attributes.get("width")?,
+                    height: attributes.get("height")?,
+                    x_offset: attributes.get("xoffset")?,
+                    y_offset: attributes.get("yoffset")?,
+                    x_advance: attributes.get("xadvance")?,
+                    page: attributes.get_or("page", 0)?,
+                }),
+                "kerning" => font.kernings.push(BmKerning {
+                    first: attributes.get("first")?,
+                    second: attributes.get("second")?,
+                    amount: attributes.get("amount")?,
+                }),
+                "distanceField" => {
+                    let kind = match attributes.get_string("fieldType")?.as_str() {
+                        "msdf" | "mtsdf" => DistanceFieldKind::MultiChannel,
+                        _ => DistanceFieldKind::SingleChannel,
+                    };
+                    font.distance_field = Some(DistanceField {
+                        kind,
+                        range: attributes.get("distanceRange")?,
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(font)
+    }
+
+    pub fn parse_binary(data: &[u8]) -> Result<BmFont, Error> {
+        let version = data.get(BINARY_MAGIC.len()).copied();
+        if version != Some(BINARY_VERSION) {
+            return Err(Error::InvalidFont(format!(
+                "unsupported binary font version {:?}",
+                version
+            )));
+        }
+
+        let mut font = BmFont::default();
+        let mut reader = Reader {
+            data,
+            position: BINARY_MAGIC.len() + 1,
+        };
+        while reader.position < data.len() {
+            let block_type = reader.u8()?;
+            let block_size = reader.u32()? as usize;
+            let block = Reader {
+                data: reader.bytes(block_size)?,
+                position: 0,
+            };
+            match block_type {
+                2 => font.parse_common_block(block)?,
+                3 => font.parse_pages_block(block),
+                4 => font.parse_chars_block(block)?,
+                5 => font.parse_kerning_block(block)?,
+                _ => {}
+            }
+        }
+        Ok(font)
+    }
+
+    fn parse_common_block(&mut self, mut block: Reader) -> Result<(), Error> {
+        self.line_height = block.u16()? as u32;
+        self.base = block.u16()? as u32;
+        Ok(())
+    }
+
+    fn parse_pages_block(&mut self, block: Reader) {
+        self.pages = block
+            .data
+            .split(|byte| *byte == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .collect();
+    }
+
+    fn parse_chars_block(&mut self, mut block: Reader) -> Result<(), Error> {
+        for _ in 0..block.data.len() / BINARY_CHAR_SIZE {
+            let id = block.u32()?;
+            let x = block.u16()? as u32;
+            let y = block.u16()? as u32;
+            let width = block.u16()? as u32;
+            let height = block.u16()? as u32;
+            let x_offset = block.i16()? as i32;
+            let y_offset = block.i16()? as i32;
+            let x_advance = block.i16()? as i32;
+            let page = block.u8()? as usize;
+            // channel
+            block.u8()?;
+            self.chars.push(BmChar {
+                id,
+                x,
+                y,
+                width,
+                height,
+                x_offset,
+                y_offset,
+                x_advance,
+                page,
+            });
+        }
+        Ok(())
+    }
+
+    fn parse_kerning_block(&mut self, mut block: Reader) -> Result<(), Error> {
+        for _ in 0..block.data.len() / BINARY_KERNING_SIZE {
+            self.kernings.push(BmKerning {
+                first: block.u32()?,
+                second: block.u32()?,
+                amount: block.i16()? as i32,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Splits a line on whitespace, keeping quoted values like `face="Some Font"` together.
+fn tokenize(line: &str) -> impl Iterator<Item = &str> {
+    let mut in_quotes = false;
+    line.split(move |ch: char| {
+        if ch == '"' {
+            in_quotes = !in_quotes;
+        }
+        ch.is_whitespace() && !in_quotes
+    })
+    .filter(|token| !token.is_empty())
+}
+
+struct Attributes<'a> {
+    values: HashMap<&'a str, &'a str>,
+    line: usize,
+}
+
+impl<'a> Attributes<'a> {
+    fn error(&self, reason: String) -> Error {
+        Error::InvalidFont(format!("line {}: {}", self.line, reason))
+    }
+
+    fn get_string(&self, key: &str) -> Result<String, Error> {
+        self.values
+            .get(key)
+            .map(|value| value.trim_matches('"').to_string())
+            .ok_or_else(|| self.error(format!("missing {}", key)))
+    }
+
+    fn get<T: std::str::FromStr>(&self, key: &str) -> Result<T, Error> {
+        let value = self.get_string(key)?;
+        value
+            .parse()
+            .map_err(|_| self.error(format!("invalid {} `{}`", key, value)))
+    }
+
+    fn get_or<T: std::str::FromStr>(&self, key: &str, default: T) -> Result<T, Error> {
+        match self.values.contains_key(key) {
+            true => self.get(key),
+            false => Ok(default),
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], Error> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + count)
+            .ok_or_else(|| Error::InvalidFont("unexpected end of binary font".to_string()))?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn i16(&mut self) -> Result<i16, Error> {
+        Ok(i16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT_FONT: &str = r#"info face="Some Font" size=16 bold=0
+common lineHeight=16 base=12 scaleW=64 scaleH=64 pages=2
+page id=1 file="second page.png"
+page id=0 file="first.png"
+chars count=2
+char id=65 x=1 y=2 width=7 height=9 xoffset=-1 yoffset=3 xadvance=8 page=1
+char id=32 x=0 y=0 width=0 height=0 xoffset=0 yoffset=0 xadvance=4
+kernings count=1
+kerning first=65 second=32 amount=-2
+"#;
+
+    fn block(block_type: u8, content: &[u8]) -> Vec<u8> {
+        let mut block = vec![block_type];
+        block.extend((content.len() as u32).to_le_bytes());
+        block.extend(content);
+        block
+    }
+
+    fn binary_font() -> Vec<u8> {
+        let mut common = vec![];
+        common.extend(16u16.to_le_bytes());
+        common.extend(12u16.to_le_bytes());
+        // scale, pages, flags and channels, which are ignored
+        common.extend([0; 11]);
+
+        let mut chars = vec![];
+        chars.extend(65u32.to_le_bytes());
+        for value in [1u16, 2, 7, 9] {
+            chars.extend(value.to_le_bytes());
+        }
+        for value in [-1i16, 3, 8] {
+            chars.extend(value.to_le_bytes());
+        }
+        chars.extend([1, 15]);
+
+        let mut kernings = vec![];
+        kernings.extend(65u32.to_le_bytes());
+        kernings.extend(32u32.to_le_bytes());
+        kernings.extend((-2i16).to_le_bytes());
+
+        let mut data = b"BMF".to_vec();
+        data.push(BINARY_VERSION);
+        data.extend(block(1, b"info block"));
+        data.extend(block(2, &common));
+        data.extend(block(3, b"first.png\0second.png\0"));
+        data.extend(block(4, &chars));
+        data.extend(block(5, &kernings));
+        data
+    }
+
+    #[test]
+    fn splits_quoted_values_together() {
+        let tokens: Vec<&str> = tokenize(r#"info  face="Some Font" size=16"#).collect();
+        assert_eq!(tokens, vec!["info", r#"face="Some Font""#, "size=16"]);
+    }
+
+    #[test]
+    fn parses_text_font() {
+        let font = BmFont::parse(TEXT_FONT.as_bytes()).unwrap();
+        assert_eq!((font.line_height, font.base), (16, 12));
+        assert_eq!(font.pages, vec!["first.png", "second page.png"]);
+        assert_eq!(font.chars.len(), 2);
+        let a = font.chars[0];
+        assert_eq!((a.id, a.x, a.y, a.width, a.height), (65, 1, 2, 7, 9));
+        assert_eq!((a.x_offset, a.y_offset, a.x_advance, a.page), (-1, 3, 8, 1));
+        assert_eq!(font.chars[1].page, 0);
+        let kerning = font.kernings[0];
+        assert_eq!(
+            (kerning.first, kerning.second, kerning.amount),
+            (65, 32, -2)
+        );
+        assert!(font.distance_field.is_none());
+    }
+
+    #[test]
+    fn reports_invalid_text_lines() {
+        let error = BmFont::parse_text("common lineHeight=16\nchar id=x").unwrap_err();
+        assert!(matches!(error, Error::InvalidFont(reason) if reason == "line 1: missing base"));
+        let error = BmFont::parse_text("char id=x").unwrap_err();
+        assert!(matches!(error, Error::InvalidFont(reason) if reason == "line 1: invalid id `x`"));
+    }
+
+    #[test]
+    fn parses_binary_font() {
+        let font = BmFont::parse(&binary_font()).unwrap();
+        assert_eq!((font.line_height, font.base), (16, 12));
+        assert_eq!(font.pages, vec!["first.png", "second.png"]);
+        assert_eq!(font.chars.len(), 1);
+        let a = font.chars[0];
+        assert_eq!((a.id, a.x, a.y, a.width, a.height), (65, 1, 2, 7, 9));
+        assert_eq!((a.x_offset, a.y_offset, a.x_advance, a.page), (-1, 3, 8, 1));
+        let kerning = font.kernings[0];
+        assert_eq!(
+            (kerning.first, kerning.second, kerning.amount),
+            (65, 32, -2)
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_binary_font() {
+        let data = binary_font();
+        for length in [data.len() - 1, data.len() - 12, 6] {
+            let error = BmFont::parse(&data[..length]).unwrap_err();
+            assert!(
+                matches!(&error, Error::InvalidFont(reason) if reason == "unexpected end of binary font"),
+                "{:?}",
+                error
+            );
+        }
+        let mut other_version = data;
+        other_version[3] = 2;
+        assert!(matches!(
+            BmFont::parse(&other_version),
+            Err(Error::InvalidFont(_))
+        ));
+    }
+
+    #[test]
+    fn places_glyph_pivot_on_line_bottom() {
+        let glyph = BmChar {
+            height: 9,
+            x_offset: -1,
+            y_offset: 3,
+            ..BmChar::default()
+        };
+        // the glyph's bottom lies 16 - 3 - 9 = 4 pixels above the bottom of the line
+        assert_eq!(glyph.pivot(16), (1, -4).into());
+    }
+}
@@ -0,0 +1,171 @@
+use crate::renderer::{Error, Renderer};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone)]
+pub enum RecordingFormat {
+    PngSequence,
+    #[allow(dead_code)]
+    Gif {
+        frame_delay_ms: u32,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct CaptureSettings {
+    pub directory: PathBuf,
+    pub every_nth_frame: u32,
+    pub recording_format: RecordingFormat,
+}
+
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        CaptureSettings {
+            directory: "captures".into(),
+            every_nth_frame: 2,
+            recording_format: RecordingFormat::PngSequence,
+        }
+    }
+}
+
+enum RecordingOutput {
+    PngSequence(PathBuf),
+    Gif {
+        encoder: Box<GifEncoder<File>>,
+        frame_delay: Delay,
+    },
+}
+
+struct Recording {
+    output: RecordingOutput,
+    frame_counter: u32,
+    saved_frames: u32,
+}
+
+/// Saves screenshots and recordings of rendered frames.
+///
+/// `capture` has to be called after every `Renderer::render`; frames are read back only
+/// while a screenshot or a recording is pending, so capturing costs nothing otherwise.
+pub struct FrameCapture {
+    settings: CaptureSettings,
+    screenshot_requested: bool,
+    recording: Option<Recording>,
+}
+
+impl FrameCapture {
+    pub fn new(settings: CaptureSettings) -> FrameCapture {
+        FrameCapture {
+            settings,
+            screenshot_requested: false,
+            recording: None,
+        }
+    }
+
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn toggle_recording(&mut self) -> Result<(), Error> {
+        if self.is_recording() {
+            self.stop_recording();
+            Ok(())
+        } else {
+            self.start_recording()
+        }
+    }
+
+    pub fn start_recording(&mut self) -> Result<(), Error> {
+        let name = format!("recording-{}", timestamp());
+        let output = match self.settings.recording_format {
+            RecordingFormat::PngSequence => {
+                let directory = self.settings.directory.join(name);
+                fs::create_dir_all(&directory)?;
+                RecordingOutput::PngSequence(directory)
+            }
+            RecordingFormat::Gif { frame_delay_ms } => {
+                fs::create_dir_all(&self.settings.directory)?;
+                let file = File::create(self.settings.directory.join(name + ".gif"))?;
+                let mut encoder = GifEncoder::new(file);
+                encoder
+                    .set_repeat(Repeat::Infinite)
+                    .map_err(Error::ImageSaveFailed)?;
+                RecordingOutput::Gif {
+                    encoder: Box::new(encoder),
+                    frame_delay: Delay::from_numer_denom_ms(frame_delay_ms, 1),
+                }
+            }
+        };
+        self.recording = Some(Recording {
+            output,
+            frame_counter: 0,
+            saved_frames: 0,
+        });
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) {
+        // dropping the encoder writes the end of the GIF file
+        self.recording = None;
+    }
+
+    pub fn capture(&mut self, renderer: &mut Renderer) -> Result<(), Error> {
+        if self.screenshot_requested || self.recording.is_some() {
+            match renderer.read_last_frame() {
+                Ok(frame) => self.save(frame)?,
+                Err(Error::NoFrameCaptured) => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        renderer.set_frame_readback(self.screenshot_requested || self.recording.is_some());
+        Ok(())
+    }
+
+    fn save(&mut self, frame: RgbaImage) -> Result<(), Error> {
+        if self.screenshot_requested {
+            fs::create_dir_all(&self.settings.directory)?;
+            let path = self
+                .settings
+                .directory
+                .join(format!("screenshot-{}.png", timestamp()));
+            frame.save(&path).map_err(Error::ImageSaveFailed)?;
+            log::info!("Saved screenshot to {}", path.display());
+            self.screenshot_requested = false;
+        }
+
+        if let Some(recording) = &mut self.recording {
+            let frame_number = recording.frame_counter;
+            recording.frame_counter += 1;
+            if frame_number % self.settings.every_nth_frame.max(1) != 0 {
+                return Ok(());
+            }
+
+            match &mut recording.output {
+                RecordingOutput::PngSequence(directory) => {
+                    let path = directory.join(format!("frame-{:05}.png", recording.saved_frames));
+                    frame.save(path).map_err(Error::ImageSaveFailed)?;
+                }
+                RecordingOutput::Gif {
+                    encoder,
+                    frame_delay,
+                } => {
+                    encoder
+                        .encode_frame(Frame::from_parts(frame, 0, 0, *frame_delay))
+                        .map_err(Error::ImageSaveFailed)?;
+                }
+            }
+            recording.saved_frames += 1;
+        }
+        Ok(())
+    }
+}
+
+fn timestamp() -> String {
+    chrono::Local::now().format("%Y%m%d-%H%M%S%.3f").to_string()
+}
@@ -0,0 +1,164 @@
+use crate::renderer::Error;
+use crate::Sprite;
+use cgmath::Vector2;
+use image::RgbaImage;
+
+const WORD_BITS: u32 = u64::BITS;
+
+/// Pixel-perfect collision shape: one bit per pixel, set where the pixel is opaque.
+///
+/// Masks are placed in the world like sprites, so a mask built from a sprite collides
+/// exactly where the sprite is drawn at the same position, pivot included.
+#[derive(Debug, Clone)]
+pub struct CollisionMask {
+    size: Vector2<u32>,
+    pivot: Vector2<i32>,
+    words_per_row: usize,
+    /// Rows ordered from the bottom one, like world coordinates.
+    bits: Vec<u64>,
+}
+
+impl CollisionMask {
+    pub fn from_sprite(sprite: &Sprite, alpha_threshold: u8) -> Result<CollisionMask, Error> {
+        let pixels = sprite.get_pixels().ok_or(Error::MissingPixelData)?;
+        Ok(Self::from_image(&pixels, alpha_threshold).with_pivot(sprite.get_pivot()))
+    }
+
+    pub fn from_image(image: &RgbaImage, alpha_threshold: u8) -> CollisionMask {
+        let (width, height) = image.dimensions();
+        let words_per_row = width.div_ceil(WORD_BITS) as usize;
+        let mut bits = vec![0; words_per_row * height as usize];
+        for (x, y, pixel) in image.enumerate_pixels() {
+            if pixel[3] > alpha_threshold {
+                let row = (height - 1 - y) as usize;
+                bits[row * words_per_row + (x / WORD_BITS) as usize] |= 1 << (x % WORD_BITS);
+            }
+        }
+        CollisionMask {
+            size: (width, height).into(),
+            pivot: (0, 0).into(),
+            words_per_row,
+            bits,
+        }
+    }
+
+    pub fn with_pivot(self, pivot: Vector2<i32>) -> CollisionMask {
+        CollisionMask { pivot, ..self }
+    }
+
+    pub fn get_size(&self) -> Vector2<u32> {
+        self.size
+    }
+
+    /// Checks a pixel, with coordinates relative to the mask's bottom-left corner.
+    pub fn is_solid(&self, x: i32, y: i32) -> bool {
+        if x < 0 || y < 0 || x >= self.size.x as i32 || y >= self.size.y as i32 {
+            return false;
+        }
+        self.bits_at(y as usize, x as u32) & 1 != 0
+    }
+
+    /// Checks whether any opaque pixels of both masks overlap, when drawn at given positions.
+    pub fn overlaps(
+        &self,
+        position: Vector2<i32>,
+        other: &CollisionMask,
+        other_position: Vector2<i32>,
+    ) -> bool {
+        let origin = position - self.pivot;
+        let other_origin = other_position - other.pivot;
+
+        let lower = Vector2::new(origin.x.max(other_origin.x), origin.y.max(other_origin.y));
+        let higher = Vector2::new(
+            (origin.x + self.size.x as i32).min(other_origin.x + other.size.x as i32),
+            (origin.y + self.size.y as i32).min(other_origin.y + other.size.y as i32),
+        );
+        if lower.x >= higher.x || lower.y >= higher.y {
+            return false;
+        }
+
+        for y in lower.y..higher.y {
+            let row = (y - origin.y) as usize;
+            let other_row = (y - other_origin.y) as usize;
+            let mut x = lower.x;
+            while x < higher.x {
+                let remaining = (higher.x - x) as u32;
+                let chunk_mask = if remaining >= WORD_BITS {
+                    u64::MAX
+                } else {
+                    (1 << remaining) - 1
+                };
+                let bits = self.bits_at(row, (x - origin.x) as u32);
+                let other_bits = other.bits_at(other_row, (x - other_origin.x) as u32);
+                if bits & other_bits & chunk_mask != 0 {
+                    return true;
+                }
+                x += WORD_BITS as i32;
+            }
+        }
+        false
+    }
+
+    /// Up to 64 bits of a row starting at `x`; bits past the row's end are zero.
+    fn bits_at(&self, row: usize, x: u32) -> u64 {
+        let row_bits = &self.bits[row * self.words_per_row..(row + 1) * self.words_per_row];
+        let word = (x / WORD_BITS) as usize;
+        let shift = x % WORD_BITS;
+        let low = row_bits.get(word).copied().unwrap_or(0) >> shift;
+        let high = match shift {
+            0 => 0,
+            _ => row_bits.get(word + 1).copied().unwrap_or(0) << (WORD_BITS - shift),
+        };
+        low | high
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    /// 70x3 image, wider than a word, with opaque pixels at (0, 0), (65, 1) and (69, 2),
+    /// counting from the top-left corner, and a translucent one at (1, 0).
+    fn test_image() -> RgbaImage {
+        let mut image = RgbaImage::new(70, 3);
+        image.put_pixel(0, 0, Rgba([255, 255, 255, 255]));
+        image.put_pixel(1, 0, Rgba([255, 255, 255, 100]));
+        image.put_pixel(65, 1, Rgba([255, 255, 255, 255]));
+        image.put_pixel(69, 2, Rgba([255, 255, 255, 255]));
+        image
+    }
+
+    #[test]
+    fn builds_mask_from_image_alpha() {
+        let mask = CollisionMask::from_image(&test_image(), 127);
+        assert_eq!(mask.get_size(), (70, 3).into());
+        // rows count from the bottom
+        assert!(mask.is_solid(0, 2));
+        assert!(!mask.is_solid(1, 2));
+        assert!(mask.is_solid(65, 1));
+        assert!(mask.is_solid(69, 0));
+        assert!(!mask.is_solid(0, 0));
+        assert!(!mask.is_solid(70, 0));
+        assert!(!mask.is_solid(-1, 2));
+
+        let low_threshold = CollisionMask::from_image(&test_image(), 50);
+        assert!(low_threshold.is_solid(1, 2));
+    }
+
+    #[test]
+    fn overlaps_only_on_opaque_pixels() {
+        let mask = CollisionMask::from_image(&test_image(), 127);
+        let mut dot_image = RgbaImage::new(1, 1);
+        dot_image.put_pixel(0, 0, Rgba([0, 0, 0, 255]));
+        let dot = CollisionMask::from_image(&dot_image, 0);
+
+        assert!(mask.overlaps((10, 10).into(), &dot, (75, 11).into()));
+        assert!(mask.overlaps((10, 10).into(), &dot, (79, 10).into()));
+        assert!(!mask.overlaps((10, 10).into(), &dot, (76, 11).into()));
+        assert!(!mask.overlaps((10, 10).into(), &dot, (80, 10).into()));
+
+        let centered = dot.with_pivot((1, 1).into());
+        assert!(mask.overlaps((10, 10).into(), &centered, (76, 12).into()));
+    }
+}
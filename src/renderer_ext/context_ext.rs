@@ -1,4 +1,9 @@
 use crate::color::Color;
+use crate::renderer_ext::animation::AnimatedSprite;
+use crate::renderer_ext::bitmap_font::TextStyle;
+use crate::renderer_ext::rich_text::RichTextLayout;
+use crate::renderer_ext::text_box::TextBox;
+use crate::renderer_ext::text_layout::TextLayout;
 use crate::{BitmapFont, Layer, RenderContext, TextAlignment};
 use cgmath::Vector2;
 
@@ -13,6 +18,50 @@ pub trait RenderContextExt {
         layer: Layer,
         color: Color,
     );
+
+    #[allow(dead_code)]
+    fn draw_styled_text(
+        &mut self,
+        font: &BitmapFont,
+        text: &str,
+        position: Vector2<i32>,
+        layer: Layer,
+        style: &TextStyle,
+    );
+
+    /// Draws text wrapped into the box.
+    #[allow(dead_code)]
+    fn draw_text_box(
+        &mut self,
+        font: &BitmapFont,
+        text: &str,
+        text_box: &TextBox,
+        layer: Layer,
+        style: &TextStyle,
+    );
+
+    /// Draws text laid out beforehand with `BitmapFont::layout`.
+    #[allow(dead_code)]
+    fn draw_text_layout(
+        &mut self,
+        font: &BitmapFont,
+        layout: &TextLayout,
+        layer: Layer,
+        style: &TextStyle,
+    );
+
+    /// Draws rich text laid out beforehand with `RichText::layout`.
+    #[allow(dead_code)]
+    fn draw_rich_text(&mut self, layout: &RichTextLayout, layer: Layer, style: &TextStyle);
+
+    #[allow(dead_code)]
+    fn draw_animation(
+        &mut self,
+        animation: &AnimatedSprite,
+        position: Vector2<i32>,
+        layer: Layer,
+        color: Color,
+    );
 }
 
 impl<'a> RenderContextExt for RenderContext<'a> {
@@ -26,6 +75,56 @@ impl<'a> RenderContextExt for RenderContext<'a> {
         layer: Layer,
         color: Color,
     ) {
-        font.draw_text(self, text, position, layer, alignment, padding, color);
+        let style = TextStyle::new(color)
+            .with_alignment(alignment.clone())
+            .with_padding(padding);
+        font.draw_text(self, text, position, layer, &style);
+    }
+
+    fn draw_styled_text(
+        &mut self,
+        font: &BitmapFont,
+        text: &str,
+        position: Vector2<i32>,
+        layer: Layer,
+        style: &TextStyle,
+    ) {
+        font.draw_text(self, text, position, layer, style);
+    }
+
+    fn draw_text_box(
+        &mut self,
+        font: &BitmapFont,
+        text: &str,
+        text_box: &TextBox,
+        layer: Layer,
+        style: &TextStyle,
+    ) {
+        let layout = font.layout_in_box(text, text_box, style);
+        font.draw_layout(self, &layout, layer, style);
+    }
+
+    fn draw_text_layout(
+        &mut self,
+        font: &BitmapFont,
+        layout: &TextLayout,
+        layer: Layer,
+        style: &TextStyle,
+    ) {
+        font.draw_layout(self, layout, layer, style);
+    }
+
+    fn draw_rich_text(&mut self, layout: &RichTextLayout, layer: Layer, style: &TextStyle) {
+        layout.draw(self, layer, style);
+    }
+
+    fn draw_animation(
+        &mut self,
+        animation: &AnimatedSprite,
+        position: Vector2<i32>,
+        layer: Layer,
+        color: Color,
+    ) {
+        self.draw_sprite(animation.current_sprite(), position, layer, color);
     }
 }
@@ -1,3 +1,21 @@
+#[allow(dead_code)]
+pub mod animation;
+#[allow(dead_code)]
+pub mod atlas;
 pub mod bitmap_font;
+#[allow(dead_code)]
+pub mod bmfont;
+pub mod capture;
+#[allow(dead_code)]
+pub mod collision;
 pub mod context_ext;
+#[allow(dead_code)]
+pub mod rich_text;
 pub mod sprite;
+pub mod sprite_sheet;
+#[allow(dead_code)]
+pub mod text_box;
+#[allow(dead_code)]
+pub mod text_layout;
+#[allow(dead_code)]
+pub mod true_type_font;
@@ -0,0 +1,507 @@
+use crate::renderer::color::{self, Color};
+use crate::renderer::Error;
+use crate::renderer_ext::bitmap_font::{BitmapFont, TextAlignment, TextAnchor, TextStyle};
+use crate::renderer_ext::text_layout::{TextBounds, TextLayout};
+use crate::{Layer, RenderContext, Sprite};
+use cgmath::Vector2;
+use std::collections::HashMap;
+
+/// Part of rich text drawn with a single style.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextRun {
+    /// Text without line breaks. Unset color and font come from the text style
+    /// and the default font.
+    Text {
+        text: String,
+        color: Option<Color>,
+        font: Option<String>,
+    },
+    /// Sprite standing on the base line, like a glyph.
+    Icon(String),
+    LineBreak,
+}
+
+/// Text parsed from markup into styled runs.
+///
+/// Supported tags are `[color=#rrggbb]...[/color]`, `[font=name]...[/font]` and `[icon=name]`;
+/// colors take any notation of `Color::from_hex`. Tags nest, and `[[` stands for a literal `[`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RichText {
+    runs: Vec<TextRun>,
+}
+
+impl RichText {
+    pub fn parse(markup: &str) -> Result<RichText, Error> {
+        let mut parser = Parser::default();
+        let mut rest = markup;
+        while let Some(tag_start) = rest.find('[') {
+            parser.push_text(&rest[..tag_start]);
+            rest = &rest[tag_start + 1..];
+            if let Some(after) = rest.strip_prefix('[') {
+                parser.push_text("[");
+                rest = after;
+                continue;
+            }
+            let tag_end = rest
+                .find(']')
+                .ok_or_else(|| Error::InvalidMarkup(format!("unclosed tag `[{}`", rest)))?;
+            parser.push_tag(&rest[..tag_end])?;
+            rest = &rest[tag_end + 1..];
+        }
+        parser.push_text(rest);
+        parser.finish()
+    }
+
+    pub fn runs(&self) -> &[TextRun] {
+        &self.runs
+    }
+
+    /// Text without markup, icons left out.
+    pub fn plain_text(&self) -> String {
+        self.runs
+            .iter()
+            .map(|run| match run {
+                TextRun::Text { text, .. } => text.as_str(),
+                TextRun::Icon(_) => "",
+                TextRun::LineBreak => "\n",
+            })
+            .collect()
+    }
+
+    /// Places every run, as it would be drawn at `position` with the style. Runs of different
+    /// fonts and icons share the base line of their line.
+    pub fn layout<'a>(
+        &self,
+        resources: &RichTextResources<'a>,
+        position: Vector2<i32>,
+        style: &TextStyle,
+    ) -> Result<RichTextLayout<'a>, Error> {
+        let lines = self.measure_lines(resources, style)?;
+        let line_gap = (style.padding.y * 2) as f32 * style.scale;
+        let block_height = lines.iter().map(|line| line.height()).sum::<f32>()
+            + lines.len().saturating_sub(1) as f32 * line_gap;
+        let first_line = &lines[0];
+        let top = position.y as f32
+            + match style.anchor {
+                TextAnchor::Top => 0.0,
+                TextAnchor::Middle => block_height / 2.0,
+                TextAnchor::Baseline => first_line.ascent,
+                TextAnchor::Bottom => block_height,
+            };
+
+        let mut items = vec![];
+        let mut bounds: Option<TextBounds> = None;
+        let mut line_top = top;
+        for line in &lines {
+            let baseline = line_top - line.ascent;
+            let left = position.x as f32
+                - match style.alignment {
+                    TextAlignment::Left | TextAlignment::Justified => 0.0,
+                    TextAlignment::Center => (line.width / 2.0).floor(),
+                    TextAlignment::Right => line.width,
+                };
+            let mut pen_x = left;
+            for run in &line.runs {
+                let pen = Vector2::new(pen_x.round() as i32, baseline.round() as i32);
+                match run {
+                    MeasuredRun::Text {
+                        text,
+                        color,
+                        font,
+                        width,
+                    } => {
+                        let run_style = TextStyle {
+                            alignment: TextAlignment::Left,
+                            anchor: TextAnchor::Baseline,
+                            color: *color,
+                            ..style.clone()
+                        };
+                        items.push(RichTextItem::Text {
+                            font,
+                            layout: font.layout(text, pen, &run_style),
+                            color: *color,
+                        });
+                        pen_x += width;
+                    }
+                    MeasuredRun::Icon { sprite, width } => {
+                        items.push(RichTextItem::Icon {
+                            sprite: sprite.clone(),
+                            position: pen,
+                        });
+                        pen_x += width;
+                    }
+                }
+                pen_x += (style.padding.x * 2) as f32 * style.scale;
+            }
+
+            let line_bounds = TextBounds {
+                origin: (
+                    left.round() as i32,
+                    (line_top - line.height()).round() as i32,
+                )
+                    .into(),
+                size: (line.width.round() as i32, line.height().round() as i32).into(),
+            };
+            bounds = Some(bounds.map_or(line_bounds, |bounds| bounds.union(&line_bounds)));
+            line_top -= line.height() + line_gap;
+        }
+        Ok(RichTextLayout {
+            items,
+            bounds: bounds.expect("rich text always has a line"),
+        })
+    }
+
+    /// Splits runs into lines, resolving their fonts and icons and measuring them in pixels
+    /// of the screen.
+    fn measure_lines<'a>(
+        &self,
+        resources: &RichTextResources<'a>,
+        style: &TextStyle,
+    ) -> Result<Vec<MeasuredLine<'a>>, Error> {
+        let scale = |value: i32| value as f32 * style.scale;
+        let gap = scale(style.padding.x * 2);
+        let default_metrics = resources.font.get_metrics();
+        let empty_line = MeasuredLine {
+            runs: vec![],
+            width: 0.0,
+            ascent: scale(default_metrics.line_height - default_metrics.baseline),
+            descent: scale(default_metrics.baseline),
+        };
+
+        let mut lines = vec![empty_line.clone()];
+        for run in &self.runs {
+            let line = lines.last_mut().expect("rich text always has a line");
+            let measured = match run {
+                TextRun::LineBreak => {
+                    lines.push(empty_line.clone());
+                    continue;
+                }
+                TextRun::Text { text, color, font } => {
+                    let font = resources.font(font.as_deref())?;
+                    let metrics = font.get_metrics();
+                    line.ascent = line
+                        .ascent
+                        .max(scale(metrics.line_height - metrics.baseline));
+                    line.descent = line.descent.max(scale(metrics.baseline));
+                    MeasuredRun::Text {
+                        text: text.clone(),
+                        color: color.unwrap_or(style.color),
+                        font,
+                        width: scale(font.line_width(text, style.padding.x)),
+                    }
+                }
+                TextRun::Icon(name) => {
+                    let sprite = resources.icon(name)?;
+                    let height = scale(sprite.get_size().y as i32 - sprite.get_pivot().y);
+                    line.ascent = line.ascent.max(height);
+                    MeasuredRun::Icon {
+                        sprite: sprite.clone(),
+                        width: scale(sprite.get_size().x as i32),
+                    }
+                }
+            };
+            if !line.runs.is_empty() {
+                line.width += gap;
+            }
+            line.width += measured.width();
+            line.runs.push(measured);
+        }
+        Ok(lines)
+    }
+}
+
+/// Fonts and icons referred to by name in rich text markup.
+#[derive(Debug, Clone)]
+pub struct RichTextResources<'a> {
+    /// Font of text outside `[font]` tags.
+    font: &'a BitmapFont,
+    fonts: HashMap<String, &'a BitmapFont>,
+    icons: HashMap<String, Sprite>,
+}
+
+impl<'a> RichTextResources<'a> {
+    pub fn new(font: &'a BitmapFont) -> RichTextResources<'a> {
+        RichTextResources {
+            font,
+            fonts: HashMap::new(),
+            icons: HashMap::new(),
+        }
+    }
+
+    pub fn with_font(mut self, name: &str, font: &'a BitmapFont) -> RichTextResources<'a> {
+        self.fonts.insert(name.to_string(), font);
+        self
+    }
+
+    pub fn with_icon(mut self, name: &str, sprite: Sprite) -> RichTextResources<'a> {
+        self.icons.insert(name.to_string(), sprite);
+        self
+    }
+
+    fn font(&self, name: Option<&str>) -> Result<&'a BitmapFont, Error> {
+        match name {
+            None => Ok(self.font),
+            Some(name) => self
+                .fonts
+                .get(name)
+                .copied()
+                .ok_or_else(|| Error::InvalidMarkup(format!("font {} not found", name))),
+        }
+    }
+
+    fn icon(&self, name: &str) -> Result<&Sprite, Error> {
+        self.icons
+            .get(name)
+            .ok_or_else(|| Error::MissingSprite(name.to_string()))
+    }
+}
+
+/// Rich text laid out by `RichText::layout`, ready to be drawn.
+#[derive(Debug, Clone)]
+pub struct RichTextLayout<'a> {
+    items: Vec<RichTextItem<'a>>,
+    bounds: TextBounds,
+}
+
+#[derive(Debug, Clone)]
+enum RichTextItem<'a> {
+    Text {
+        font: &'a BitmapFont,
+        layout: TextLayout,
+        color: Color,
+    },
+    Icon {
+        sprite: Sprite,
+        position: Vector2<i32>,
+    },
+}
+
+impl<'a> RichTextLayout<'a> {
+    /// Bounding box of all lines.
+    pub fn bounds(&self) -> TextBounds {
+        self.bounds
+    }
+
+    pub fn size(&self) -> Vector2<i32> {
+        self.bounds.size
+    }
+
+    /// Draws text runs in their colors and icons untinted, otherwise as set by the style.
+    pub(in crate::renderer_ext) fn draw(
+        &self,
+        ctx: &mut RenderContext,
+        layer: Layer,
+        style: &TextStyle,
+    ) {
+        for item in &self.items {
+            match item {
+                RichTextItem::Text {
+                    font,
+                    layout,
+                    color,
+                } => {
+                    let run_style = TextStyle {
+                        color: *color,
+                        ..style.clone()
+                    };
+                    font.draw_layout(ctx, layout, layer, &run_style);
+                }
+                RichTextItem::Icon { sprite, position } => {
+                    ctx.draw_sprite_scaled(sprite, *position, layer, color::WHITE, style.scale);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct MeasuredLine<'a> {
+    runs: Vec<MeasuredRun<'a>>,
+    width: f32,
+    /// Heights above and below the base line, of the tallest fonts and icons in the line.
+    ascent: f32,
+    descent: f32,
+}
+
+impl<'a> MeasuredLine<'a> {
+    fn height(&self) -> f32 {
+        self.ascent + self.descent
+    }
+}
+
+#[derive(Debug, Clone)]
+enum MeasuredRun<'a> {
+    Text {
+        text: String,
+        color: Color,
+        font: &'a BitmapFont,
+        width: f32,
+    },
+    Icon {
+        sprite: Sprite,
+        width: f32,
+    },
+}
+
+impl<'a> MeasuredRun<'a> {
+    fn width(&self) -> f32 {
+        match self {
+            MeasuredRun::Text { width, .. } | MeasuredRun::Icon { width, .. } => *width,
+        }
+    }
+}
+
+/// Builds runs out of text and tags, keeping the stacks of open tags.
+#[derive(Default)]
+struct Parser {
+    runs: Vec<TextRun>,
+    text: String,
+    colors: Vec<Color>,
+    fonts: Vec<String>,
+}
+
+impl Parser {
+    fn push_text(&mut self, text: &str) {
+        for (line_id, line) in text.split('\n').enumerate() {
+            if line_id > 0 {
+                self.flush();
+                self.runs.push(TextRun::LineBreak);
+            }
+            self.text.push_str(line.trim_end_matches('\r'));
+        }
+    }
+
+    fn push_tag(&mut self, tag: &str) -> Result<(), Error> {
+        let (name, value) = match tag.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim())),
+            None => (tag.trim(), None),
+        };
+        // text before the tag keeps the styles it was written with
+        self.flush();
+        match (name, value) {
+            ("color", Some(value)) => self.colors.push(Color::from_hex(value)?),
+            ("font", Some(value)) => self.fonts.push(value.to_string()),
+            ("icon", Some(value)) => self.runs.push(TextRun::Icon(value.to_string())),
+            ("/color", None) => {
+                self.colors
+                    .pop()
+                    .ok_or_else(|| Error::InvalidMarkup("unopened [/color] tag".to_string()))?;
+            }
+            ("/font", None) => {
+                self.fonts
+                    .pop()
+                    .ok_or_else(|| Error::InvalidMarkup("unopened [/font] tag".to_string()))?;
+            }
+            _ => return Err(Error::InvalidMarkup(format!("unknown tag [{}]", tag))),
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) {
+        if !self.text.is_empty() {
+            self.runs.push(TextRun::Text {
+                text: std::mem::take(&mut self.text),
+                color: self.colors.last().copied(),
+                font: self.fonts.last().cloned(),
+            });
+        }
+    }
+
+    fn finish(mut self) -> Result<RichText, Error> {
+        self.flush();
+        if !self.colors.is_empty() {
+            return Err(Error::InvalidMarkup("unclosed [color] tag".to_string()));
+        }
+        if !self.fonts.is_empty() {
+            return Err(Error::InvalidMarkup("unclosed [font] tag".to_string()));
+        }
+        Ok(RichText { runs: self.runs })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::color::{BLUE, RED};
+
+    fn text(text: &str, color: Option<Color>, font: Option<&str>) -> TextRun {
+        TextRun::Text {
+            text: text.to_string(),
+            color,
+            font: font.map(str::to_string),
+        }
+    }
+
+    fn is_invalid_markup(markup: &str) -> bool {
+        matches!(RichText::parse(markup), Err(Error::InvalidMarkup(_)))
+    }
+
+    #[test]
+    fn parses_nested_tags() {
+        let rich_text =
+            RichText::parse("a[color=#f00]b[font=bold]c[color=00f]d[/color][/font]e[/color]f")
+                .unwrap();
+        assert_eq!(
+            rich_text.runs(),
+            &[
+                text("a", None, None),
+                text("b", Some(RED), None),
+                text("c", Some(RED), Some("bold")),
+                text("d", Some(BLUE), Some("bold")),
+                text("e", Some(RED), None),
+                text("f", None, None),
+            ]
+        );
+        assert_eq!(rich_text.plain_text(), "abcdef");
+    }
+
+    #[test]
+    fn parses_icons_and_escaped_brackets() {
+        let rich_text = RichText::parse("Press [icon=a_button] to [[jump]").unwrap();
+        assert_eq!(
+            rich_text.runs(),
+            &[
+                text("Press ", None, None),
+                TextRun::Icon("a_button".to_string()),
+                text(" to [jump]", None, None),
+            ]
+        );
+        assert_eq!(rich_text.plain_text(), "Press  to [jump]");
+    }
+
+    #[test]
+    fn splits_lines_on_line_breaks() {
+        let rich_text = RichText::parse("one\r\n[color=#f00]two\nthree[/color]").unwrap();
+        assert_eq!(
+            rich_text.runs(),
+            &[
+                text("one", None, None),
+                TextRun::LineBreak,
+                text("two", Some(RED), None),
+                TextRun::LineBreak,
+                text("three", Some(RED), None),
+            ]
+        );
+        assert_eq!(rich_text.plain_text(), "one\ntwo\nthree");
+    }
+
+    #[test]
+    fn rejects_invalid_markup() {
+        assert!(is_invalid_markup("[color=#f00]red"));
+        assert!(is_invalid_markup("text[/font]"));
+        assert!(is_invalid_markup("[bold]text[/bold]"));
+        assert!(is_invalid_markup("[color=#f00"));
+        assert!(is_invalid_markup("[icon]"));
+        assert!(matches!(
+            RichText::parse("[color=red]text[/color]"),
+            Err(Error::InvalidColor(_))
+        ));
+    }
+
+    #[test]
+    fn parses_empty_text() {
+        let rich_text = RichText::parse("").unwrap();
+        assert!(rich_text.runs().is_empty());
+        assert_eq!(rich_text.plain_text(), "");
+    }
+}
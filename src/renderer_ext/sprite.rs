@@ -1,59 +1,498 @@
+use crate::renderer::Error;
 use crate::Sprite;
+use cgmath::Vector2;
+use image::RgbaImage;
 use std::str::SplitInclusive;
 
+#[derive(Debug, Copy, Clone)]
 pub enum GridMode {
     CellSize(cgmath::Vector2<u32>),
     CellCount(cgmath::Vector2<usize>),
 }
 
-pub trait SpriteExt {
-    fn uniform_grid(&self, grid_mode: GridMode) -> Vec<Sprite>;
-    fn non_uniform_grid(&self, rows: &[u32], cols: &[u32]) -> Vec<Sprite>;
+#[derive(Debug, Copy, Clone, Default)]
+pub enum GridOrder {
+    #[default]
+    RowMajor,
+    #[allow(dead_code)]
+    ColumnMajor,
 }
 
-impl SpriteExt for Sprite {
-    fn uniform_grid(&self, grid_mode: GridMode) -> Vec<Sprite> {
-        use GridMode::*;
-        let cell_count = match grid_mode {
-            CellSize(size) => (
-                (self.get_size().x / size.x) as usize,
-                (self.get_size().y / size.y) as usize,
-            )
-                .into(),
-            CellCount(count) => count,
+/// Layout of a uniform grid, for sheets whose cells are not tightly packed.
+#[derive(Debug, Copy, Clone)]
+pub struct GridLayout {
+    mode: GridMode,
+    margin: Vector2<u32>,
+    spacing: Vector2<u32>,
+    limit: Option<usize>,
+    order: GridOrder,
+}
+
+#[allow(dead_code)]
+impl GridLayout {
+    pub fn new(mode: GridMode) -> GridLayout {
+        GridLayout {
+            mode,
+            margin: (0, 0).into(),
+            spacing: (0, 0).into(),
+            limit: None,
+            order: GridOrder::default(),
+        }
+    }
+
+    /// Empty space around the whole grid.
+    pub fn with_margin(self, margin: Vector2<u32>) -> GridLayout {
+        GridLayout { margin, ..self }
+    }
+
+    /// Empty space between neighbouring cells.
+    pub fn with_spacing(self, spacing: Vector2<u32>) -> GridLayout {
+        GridLayout { spacing, ..self }
+    }
+
+    /// Maximal number of returned cells, for sheets with an incomplete last row or column.
+    pub fn with_limit(self, limit: usize) -> GridLayout {
+        GridLayout {
+            limit: Some(limit),
+            ..self
+        }
+    }
+
+    pub fn with_order(self, order: GridOrder) -> GridLayout {
+        GridLayout { order, ..self }
+    }
+
+    /// Cells of the grid laid over a sheet of the given size, in the order of the layout.
+    fn cells(&self, sprite_size: Vector2<u32>) -> Result<GridCells, Error> {
+        let mismatch = || Error::GridMismatch {
+            sprite_size,
+            margin: self.margin,
+            spacing: self.spacing,
         };
-        let cell_size = match grid_mode {
-            CellSize(size) => size,
-            CellCount(count) => (
-                self.get_size().x / count.x as u32,
-                self.get_size().y / count.y as u32,
+        let available = Vector2::new(
+            sprite_size
+                .x
+                .checked_sub(2 * self.margin.x)
+                .ok_or_else(mismatch)?,
+            sprite_size
+                .y
+                .checked_sub(2 * self.margin.y)
+                .ok_or_else(mismatch)?,
+        );
+
+        let (cell_count, cell_size) = match self.mode {
+            GridMode::CellSize(size) => {
+                if size.x == 0 || size.y == 0 {
+                    return Err(Error::EmptyGrid);
+                }
+                let count = Vector2::new(
+                    (available.x + self.spacing.x) / (size.x + self.spacing.x),
+                    (available.y + self.spacing.y) / (size.y + self.spacing.y),
+                );
+                (count, size)
+            }
+            GridMode::CellCount(count) => {
+                if count.x == 0 || count.y == 0 {
+                    return Err(Error::EmptyGrid);
+                }
+                let count = Vector2::new(count.x as u32, count.y as u32);
+                let cells_space = Vector2::new(
+                    available
+                        .x
+                        .checked_sub((count.x - 1) * self.spacing.x)
+                        .ok_or_else(mismatch)?,
+                    available
+                        .y
+                        .checked_sub((count.y - 1) * self.spacing.y)
+                        .ok_or_else(mismatch)?,
+                );
+                (
+                    count,
+                    (cells_space.x / count.x, cells_space.y / count.y).into(),
+                )
+            }
+        };
+
+        let covered =
+            |count: u32, size: u32, spacing: u32| count * size + count.saturating_sub(1) * spacing;
+        if cell_count.x == 0
+            || cell_count.y == 0
+            || covered(cell_count.x, cell_size.x, self.spacing.x) != available.x
+            || covered(cell_count.y, cell_size.y, self.spacing.y) != available.y
+        {
+            return Err(mismatch());
+        }
+
+        let cell_offset = |x: u32, y: u32| {
+            Vector2::new(
+                self.margin.x + x * (cell_size.x + self.spacing.x),
+                self.margin.y + y * (cell_size.y + self.spacing.y),
             )
-                .into(),
         };
-        let mut cells = vec![];
-        for y in 0..cell_count.y {
-            for x in 0..cell_count.x {
-                let offset = (x as u32 * cell_size.x, y as u32 * cell_size.y).into();
-                let slice = self.slice(cell_size, offset);
-                cells.push(slice)
-            }
+        let offsets = match self.order {
+            GridOrder::RowMajor => (0..cell_count.y)
+                .flat_map(|y| (0..cell_count.x).map(move |x| (x, y)))
+                .map(|(x, y)| cell_offset(x, y))
+                .take(self.limit.unwrap_or(usize::MAX))
+                .collect(),
+            GridOrder::ColumnMajor => (0..cell_count.x)
+                .flat_map(|x| (0..cell_count.y).map(move |y| (x, y)))
+                .map(|(x, y)| cell_offset(x, y))
+                .take(self.limit.unwrap_or(usize::MAX))
+                .collect(),
+        };
+        Ok(GridCells {
+            count: cell_count,
+            size: cell_size,
+            offsets,
+        })
+    }
+}
+
+/// Cells of a grid; `offsets` are relative to the sheet's top-left corner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct GridCells {
+    count: Vector2<u32>,
+    size: Vector2<u32>,
+    offsets: Vec<Vector2<u32>>,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct AutoSliceSettings {
+    /// Pixels with alpha above the threshold are treated as opaque.
+    pub alpha_threshold: u8,
+    /// Regions with fewer opaque pixels are treated as noise and skipped.
+    pub min_pixels: usize,
+}
+
+impl Default for AutoSliceSettings {
+    fn default() -> Self {
+        AutoSliceSettings {
+            alpha_threshold: 0,
+            min_pixels: 1,
         }
+    }
+}
+
+pub trait SpriteExt {
+    fn uniform_grid(&self, grid_mode: GridMode) -> Result<Vec<Sprite>, Error>;
+    fn uniform_grid_with_layout(&self, layout: GridLayout) -> Result<Vec<Sprite>, Error>;
+    fn non_uniform_grid(&self, rows: &[u32], cols: &[u32]) -> Result<Vec<Sprite>, Error>;
+    /// Slices the sprite into bounding boxes of its connected opaque regions, sorted in
+    /// reading order. `pixels` are the sprite's own pixels, e.g. from `Renderer::read_sprite`.
+    #[allow(dead_code)]
+    fn auto_slice(
+        &self,
+        pixels: &RgbaImage,
+        settings: AutoSliceSettings,
+    ) -> Result<Vec<Sprite>, Error>;
+}
+
+impl SpriteExt for Sprite {
+    fn uniform_grid(&self, grid_mode: GridMode) -> Result<Vec<Sprite>, Error> {
+        self.uniform_grid_with_layout(GridLayout::new(grid_mode))
+    }
+
+    fn uniform_grid_with_layout(&self, layout: GridLayout) -> Result<Vec<Sprite>, Error> {
+        let cells = layout.cells(self.get_size())?;
         cells
+            .offsets
+            .into_iter()
+            .map(|offset| self.slice(cells.size, offset))
+            .collect()
     }
 
-    fn non_uniform_grid(&self, rows: &[u32], cols: &[u32]) -> Vec<Sprite> {
+    fn non_uniform_grid(&self, rows: &[u32], cols: &[u32]) -> Result<Vec<Sprite>, Error> {
         let mut cells = vec![];
         let mut offset = (0, 0).into();
         for row_size in rows {
             for col_size in cols {
                 let cell_size = (*col_size, *row_size).into();
-                let slice = self.slice(cell_size, offset);
+                let slice = self.slice(cell_size, offset)?;
                 cells.push(slice);
                 offset.x += col_size;
             }
             offset.x = 0;
             offset.y += row_size;
         }
-        cells
+        Ok(cells)
+    }
+
+    fn auto_slice(
+        &self,
+        pixels: &RgbaImage,
+        settings: AutoSliceSettings,
+    ) -> Result<Vec<Sprite>, Error> {
+        let regions = find_opaque_regions(pixels, settings);
+        sort_in_reading_order(regions)
+            .into_iter()
+            .map(|region| self.slice(region.size(), region.min))
+            .collect()
+    }
+}
+
+/// Prepares a tightly packed grid atlas for drawing without seams: every cell gets
+/// surrounded by `extrusion` copies of its edge pixels. Returns the new image together with
+/// the layout which slices it back into the original cells.
+#[allow(dead_code)]
+pub fn extrude_cells(
+    image: &RgbaImage,
+    cell_size: Vector2<u32>,
+    extrusion: u32,
+) -> Result<(RgbaImage, GridLayout), Error> {
+    if cell_size.x == 0 || cell_size.y == 0 {
+        return Err(Error::EmptyGrid);
+    }
+    let (width, height) = image.dimensions();
+    if width % cell_size.x != 0 || height % cell_size.y != 0 {
+        return Err(Error::GridMismatch {
+            sprite_size: (width, height).into(),
+            margin: (0, 0).into(),
+            spacing: (0, 0).into(),
+        });
+    }
+
+    let cell_count = Vector2::new(width / cell_size.x, height / cell_size.y);
+    let extruded_cell_size = cell_size + Vector2::new(2 * extrusion, 2 * extrusion);
+    let mut extruded = RgbaImage::new(
+        cell_count.x * extruded_cell_size.x,
+        cell_count.y * extruded_cell_size.y,
+    );
+    for (x, y, pixel) in extruded.enumerate_pixels_mut() {
+        let cell = Vector2::new(x / extruded_cell_size.x, y / extruded_cell_size.y);
+        let in_cell = Vector2::new(x % extruded_cell_size.x, y % extruded_cell_size.y);
+        let source_x = in_cell.x.saturating_sub(extrusion).min(cell_size.x - 1);
+        let source_y = in_cell.y.saturating_sub(extrusion).min(cell_size.y - 1);
+        *pixel = *image.get_pixel(
+            cell.x * cell_size.x + source_x,
+            cell.y * cell_size.y + source_y,
+        );
+    }
+
+    let layout = GridLayout::new(GridMode::CellSize(cell_size))
+        .with_margin((extrusion, extrusion).into())
+        .with_spacing((2 * extrusion, 2 * extrusion).into());
+    Ok((extruded, layout))
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Region {
+    min: Vector2<u32>,
+    max: Vector2<u32>,
+}
+
+impl Region {
+    fn size(&self) -> Vector2<u32> {
+        self.max - self.min + Vector2::new(1, 1)
+    }
+}
+
+fn find_opaque_regions(pixels: &RgbaImage, settings: AutoSliceSettings) -> Vec<Region> {
+    let (width, height) = pixels.dimensions();
+    let is_opaque = |x: u32, y: u32| pixels.get_pixel(x, y)[3] > settings.alpha_threshold;
+    let mut visited = vec![false; (width * height) as usize];
+    let mut regions = vec![];
+
+    for start_y in 0..height {
+        for start_x in 0..width {
+            if visited[(start_y * width + start_x) as usize] || !is_opaque(start_x, start_y) {
+                continue;
+            }
+
+            // flood fill with diagonal neighbours, so thin diagonal lines stay in one region
+            let mut region = Region {
+                min: (start_x, start_y).into(),
+                max: (start_x, start_y).into(),
+            };
+            let mut pixels_count = 0;
+            let mut stack = vec![(start_x, start_y)];
+            visited[(start_y * width + start_x) as usize] = true;
+            while let Some((x, y)) = stack.pop() {
+                pixels_count += 1;
+                region.min = (region.min.x.min(x), region.min.y.min(y)).into();
+                region.max = (region.max.x.max(x), region.max.y.max(y)).into();
+
+                for ny in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+                    for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                        let id = (ny * width + nx) as usize;
+                        if !visited[id] && is_opaque(nx, ny) {
+                            visited[id] = true;
+                            stack.push((nx, ny));
+                        }
+                    }
+                }
+            }
+
+            if pixels_count >= settings.min_pixels {
+                regions.push(region);
+            }
+        }
+    }
+    regions
+}
+
+/// Groups regions into rows of vertically overlapping regions, then orders each row
+/// from left to right.
+fn sort_in_reading_order(mut regions: Vec<Region>) -> Vec<Region> {
+    regions.sort_by_key(|r| (r.min.y, r.min.x));
+    let mut sorted = Vec::with_capacity(regions.len());
+    let mut row: Vec<Region> = vec![];
+    let mut row_bottom = 0;
+    for region in regions {
+        if !row.is_empty() && region.min.y > row_bottom {
+            row.sort_by_key(|r| r.min.x);
+            sorted.append(&mut row);
+        }
+        row_bottom = if row.is_empty() {
+            region.max.y
+        } else {
+            row_bottom.max(region.max.y)
+        };
+        row.push(region);
+    }
+    row.sort_by_key(|r| r.min.x);
+    sorted.append(&mut row);
+    sorted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offsets(points: &[(u32, u32)]) -> Vec<Vector2<u32>> {
+        points.iter().map(|point| (*point).into()).collect()
+    }
+
+    #[test]
+    fn places_cells_with_margin_and_spacing() {
+        let layout = GridLayout::new(GridMode::CellSize((4, 3).into()))
+            .with_margin((1, 2).into())
+            .with_spacing((2, 1).into());
+        assert_eq!(
+            layout.cells((12, 11).into()).unwrap(),
+            GridCells {
+                count: (2, 2).into(),
+                size: (4, 3).into(),
+                offsets: offsets(&[(1, 2), (7, 2), (1, 6), (7, 6)]),
+            }
+        );
+    }
+
+    #[test]
+    fn divides_sheet_into_cell_count() {
+        let layout =
+            GridLayout::new(GridMode::CellCount((3, 1).into())).with_spacing((1, 0).into());
+        let cells = layout.cells((11, 5).into()).unwrap();
+        assert_eq!(cells.size, (3, 5).into());
+        assert_eq!(cells.offsets, offsets(&[(0, 0), (4, 0), (8, 0)]));
+    }
+
+    #[test]
+    fn orders_and_limits_cells() {
+        let layout = GridLayout::new(GridMode::CellSize((2, 2).into()))
+            .with_order(GridOrder::ColumnMajor)
+            .with_limit(3);
+        let cells = layout.cells((4, 4).into()).unwrap();
+        assert_eq!(cells.count, (2, 2).into());
+        assert_eq!(cells.offsets, offsets(&[(0, 0), (0, 2), (2, 0)]));
+    }
+
+    #[test]
+    fn rejects_mismatched_grids() {
+        let is_mismatch = |layout: GridLayout, sheet: (u32, u32)| {
+            matches!(layout.cells(sheet.into()), Err(Error::GridMismatch { .. }))
+        };
+        // sheet not divisible into cells
+        assert!(is_mismatch(
+            GridLayout::new(GridMode::CellSize((4, 4).into())),
+            (10, 8)
+        ));
+        assert!(is_mismatch(
+            GridLayout::new(GridMode::CellCount((3, 1).into())),
+            (10, 8)
+        ));
+        // margins larger than the sheet
+        let margin = GridLayout::new(GridMode::CellSize((1, 1).into())).with_margin((3, 0).into());
+        assert!(is_mismatch(margin, (4, 4)));
+        // cells larger than the sheet
+        assert!(is_mismatch(
+            GridLayout::new(GridMode::CellSize((8, 8).into())),
+            (4, 4)
+        ));
+        // spacing leaving no room for cells
+        let spacing =
+            GridLayout::new(GridMode::CellCount((3, 1).into())).with_spacing((2, 0).into());
+        assert!(is_mismatch(spacing, (3, 1)));
+        assert!(matches!(
+            GridLayout::new(GridMode::CellSize((0, 4).into())).cells((8, 8).into()),
+            Err(Error::EmptyGrid)
+        ));
+    }
+
+    fn image(rows: &[&str]) -> RgbaImage {
+        RgbaImage::from_fn(rows[0].len() as u32, rows.len() as u32, |x, y| {
+            match rows[y as usize].as_bytes()[x as usize] {
+                b'#' => image::Rgba([0, 0, 0, 255]),
+                _ => image::Rgba([0, 0, 0, 0]),
+            }
+        })
+    }
+
+    fn region(min: (u32, u32), max: (u32, u32)) -> Region {
+        Region {
+            min: min.into(),
+            max: max.into(),
+        }
+    }
+
+    #[test]
+    fn joins_diagonal_pixels_into_regions() {
+        let pixels = image(&[
+            "#...#", //
+            ".#..#", "..#..", ".....", "##...",
+        ]);
+        let regions = find_opaque_regions(&pixels, AutoSliceSettings::default());
+        assert_eq!(
+            regions,
+            vec![
+                region((0, 0), (2, 2)),
+                region((4, 0), (4, 1)),
+                region((0, 4), (1, 4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_regions_below_min_pixels() {
+        let pixels = image(&[
+            "#..##", //
+            "...##",
+        ]);
+        let settings = AutoSliceSettings {
+            min_pixels: 2,
+            ..AutoSliceSettings::default()
+        };
+        assert_eq!(
+            find_opaque_regions(&pixels, settings),
+            vec![region((3, 0), (4, 1))]
+        );
+    }
+
+    #[test]
+    fn sorts_rows_of_uneven_regions() {
+        // the tall region starts above the short one on its left, yet both form one row
+        let regions = vec![
+            region((0, 6), (2, 7)),
+            region((5, 0), (7, 4)),
+            region((0, 2), (2, 3)),
+        ];
+        assert_eq!(
+            sort_in_reading_order(regions),
+            vec![
+                region((0, 2), (2, 3)),
+                region((5, 0), (7, 4)),
+                region((0, 6), (2, 7)),
+            ]
+        );
     }
 }
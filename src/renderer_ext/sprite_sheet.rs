@@ -0,0 +1,150 @@
+use crate::renderer::{Error, Renderer};
+use crate::Sprite;
+use cgmath::Vector2;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+pub const METADATA_EXTENSION: &str = "sheet";
+
+/// Named sprites cut out of a single image, as described by a metadata file.
+///
+/// Each non-empty line of the metadata that is not a `#` comment describes one entry,
+/// with coordinates in pixels relative to the top-left corner of the image:
+///
+/// ```text
+/// region <name> <x> <y> <width> <height> [pivot <x> <y>]
+/// grid <name> <x> <y> <cell width> <cell height> <columns> <rows> [pivot <x> <y>]
+/// ```
+///
+/// Grid cells are stored in row-major order, both as a list and as `<name>_<index>` sprites.
+/// Pivots are relative to the bottom-left corner of a sprite, like drawing positions.
+#[derive(Debug, Clone, Default)]
+pub struct SpriteSheet {
+    pub sprites: HashMap<String, Sprite>,
+    pub grids: HashMap<String, Vec<Sprite>>,
+}
+
+impl SpriteSheet {
+    /// Loads an image together with its metadata file, which has the same path
+    /// but the `.sheet` extension.
+    pub fn load(renderer: &mut Renderer, image_path: &str) -> Result<SpriteSheet, Error> {
+        let metadata_path = Path::new(image_path).with_extension(METADATA_EXTENSION);
+        let metadata = fs::read_to_string(metadata_path)?;
+        let sprite = renderer.load_sprite(image_path)?;
+        Self::parse(&sprite, &metadata)
+    }
+
+    pub fn parse(sprite: &Sprite, metadata: &str) -> Result<SpriteSheet, Error> {
+        let mut sheet = SpriteSheet::default();
+        for (line_id, line) in metadata.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut entry = Entry {
+                tokens: line.split_whitespace().collect(),
+                position: 0,
+                line: line_id + 1,
+            };
+            sheet.parse_entry(sprite, &mut entry)?;
+        }
+        Ok(sheet)
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Sprite, Error> {
+        self.sprites
+            .get(name)
+            .ok_or_else(|| Error::MissingSprite(name.to_string()))
+    }
+
+    pub fn grid(&self, name: &str) -> Result<&[Sprite], Error> {
+        self.grids
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or_else(|| Error::MissingSprite(name.to_string()))
+    }
+
+    fn parse_entry(&mut self, sprite: &Sprite, entry: &mut Entry) -> Result<(), Error> {
+        let kind = entry.next_token("entry kind")?;
+        let name = entry.next_token("name")?.to_string();
+        let offset = entry.next_vector("position")?;
+        let size = entry.next_vector("size")?;
+
+        match kind {
+            "region" => {
+                let pivot = entry.pivot()?;
+                self.sprites
+                    .insert(name, sprite.slice(size, offset)?.with_pivot(pivot));
+            }
+            "grid" => {
+                let count = entry.next_vector("cell count")?;
+                let pivot = entry.pivot()?;
+                let mut cells = vec![];
+                for y in 0..count.y {
+                    for x in 0..count.x {
+                        let cell_offset = offset + Vector2::new(x * size.x, y * size.y);
+                        let cell = sprite.slice(size, cell_offset)?.with_pivot(pivot);
+                        self.sprites
+                            .insert(format!("{}_{}", name, cells.len()), cell.clone());
+                        cells.push(cell);
+                    }
+                }
+                self.grids.insert(name, cells);
+            }
+            _ => return Err(entry.error(format!("unknown entry kind `{}`", kind))),
+        }
+
+        match entry.tokens.get(entry.position) {
+            Some(token) => Err(entry.error(format!("unexpected `{}`", token))),
+            None => Ok(()),
+        }
+    }
+}
+
+struct Entry<'a> {
+    tokens: Vec<&'a str>,
+    position: usize,
+    line: usize,
+}
+
+impl<'a> Entry<'a> {
+    fn error(&self, reason: String) -> Error {
+        Error::InvalidSpriteSheet {
+            line: self.line,
+            reason,
+        }
+    }
+
+    fn next_token(&mut self, expected: &str) -> Result<&'a str, Error> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .ok_or_else(|| self.error(format!("missing {}", expected)))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn next_number<T: std::str::FromStr>(&mut self, expected: &str) -> Result<T, Error> {
+        let token = self.next_token(expected)?;
+        token
+            .parse()
+            .map_err(|_| self.error(format!("invalid {} `{}`", expected, token)))
+    }
+
+    fn next_vector<T: std::str::FromStr>(&mut self, expected: &str) -> Result<Vector2<T>, Error> {
+        Ok(Vector2::new(
+            self.next_number(expected)?,
+            self.next_number(expected)?,
+        ))
+    }
+
+    fn pivot(&mut self) -> Result<Vector2<i32>, Error> {
+        if self.tokens.get(self.position) == Some(&"pivot") {
+            self.position += 1;
+            self.next_vector("pivot")
+        } else {
+            Ok((0, 0).into())
+        }
+    }
+}
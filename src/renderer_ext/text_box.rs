@@ -0,0 +1,414 @@
+use crate::renderer_ext::bitmap_font::{BitmapFont, TextAlignment, TextStyle};
+use crate::renderer_ext::text_layout::{graphemes, LineRange, TextBounds, TextLayout, TextLine};
+use unicode_segmentation::UnicodeSegmentation;
+
+const ELLIPSIS: &str = "...";
+
+/// Where lines are placed inside a text box.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum VerticalAlignment {
+    #[default]
+    Top,
+    Middle,
+    /// Base line of the first line goes the font's ascent below the top edge, so the
+    /// tallest glyphs touch it.
+    Baseline,
+    Bottom,
+}
+
+/// What happens to text which does not fit inside its box.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum TextOverflow {
+    /// Lines continue below the box.
+    #[default]
+    Visible,
+    /// Lines and glyphs which do not fit are not drawn.
+    Clip,
+    /// Like `Clip`, with the last drawn line ending with an ellipsis.
+    Ellipsis,
+}
+
+/// Rectangle text is wrapped into. Horizontal alignment and padding come from `TextStyle`.
+#[derive(Debug, Copy, Clone)]
+pub struct TextBox {
+    pub bounds: TextBounds,
+    pub vertical_alignment: VerticalAlignment,
+    pub overflow: TextOverflow,
+}
+
+impl TextBox {
+    pub fn new(bounds: TextBounds) -> TextBox {
+        TextBox {
+            bounds,
+            vertical_alignment: VerticalAlignment::default(),
+            overflow: TextOverflow::default(),
+        }
+    }
+
+    pub fn with_vertical_alignment(self, vertical_alignment: VerticalAlignment) -> TextBox {
+        TextBox {
+            vertical_alignment,
+            ..self
+        }
+    }
+
+    pub fn with_overflow(self, overflow: TextOverflow) -> TextBox {
+        TextBox { overflow, ..self }
+    }
+
+    pub(in crate::renderer_ext) fn layout(
+        &self,
+        font: &BitmapFont,
+        text: &str,
+        style: &TextStyle,
+    ) -> TextLayout {
+        let wrapper = Wrapper {
+            font,
+            text,
+            padding: style.padding.x,
+            max_width: self.bounds.size.x as f32 / style.scale,
+        };
+        let mut lines = wrapper.wrap();
+
+        let metrics = font.get_metrics();
+        let line_height = metrics.line_height as f32 * style.scale;
+        let line_gap = (style.padding.y * 2) as f32 * style.scale;
+        if self.overflow != TextOverflow::Visible {
+            let fitting = ((self.bounds.size.y as f32 + line_gap) / (line_height + line_gap))
+                .floor()
+                .max(0.0) as usize;
+            if fitting == 0 {
+                // boxes lower than a line show nothing, yet their layout keeps a line
+                let empty_line = TextLine {
+                    bounds: TextBounds {
+                        origin: self.bounds.origin,
+                        size: (0, 0).into(),
+                    },
+                    start: 0,
+                    end: 0,
+                };
+                return TextLayout::new(vec![], vec![empty_line]);
+            }
+            if fitting < lines.len() {
+                lines.truncate(fitting);
+                if self.overflow == TextOverflow::Ellipsis {
+                    if let Some(last) = lines.last_mut() {
+                        wrapper.add_ellipsis(last);
+                    }
+                }
+            }
+        }
+
+        let total_height = font.block_height(lines.len(), style);
+        let bottom = self.bounds.origin.y as f32;
+        let top = bottom + self.bounds.size.y as f32;
+        let first_line_y = match self.vertical_alignment {
+            VerticalAlignment::Top => top - line_height,
+            VerticalAlignment::Middle => {
+                bottom + (self.bounds.size.y as f32 + total_height) / 2.0 - line_height
+            }
+            VerticalAlignment::Baseline => {
+                top - (metrics.ascent + metrics.baseline) as f32 * style.scale
+            }
+            VerticalAlignment::Bottom => bottom + total_height - line_height,
+        };
+        let x = self.bounds.origin.x
+            + match style.alignment {
+                TextAlignment::Left | TextAlignment::Justified => 0,
+                TextAlignment::Center => self.bounds.size.x / 2,
+                TextAlignment::Right => self.bounds.size.x,
+            };
+
+        let mut layout = font.layout_lines(
+            text,
+            &lines,
+            (x, first_line_y.round() as i32).into(),
+            style,
+            Some(self.bounds.size.x as f32),
+        );
+        if self.overflow != TextOverflow::Visible {
+            layout.clip_horizontally(&self.bounds);
+        }
+        layout
+    }
+}
+
+/// Breaks text into lines no wider than `max_width`, in pixels of the font.
+struct Wrapper<'a> {
+    font: &'a BitmapFont,
+    text: &'a str,
+    padding: i32,
+    max_width: f32,
+}
+
+impl<'a> Wrapper<'a> {
+    fn wrap(&self) -> Vec<LineRange> {
+        let mut lines = vec![];
+        for paragraph in LineRange::split_lines(self.text) {
+            self.wrap_paragraph(&paragraph, &mut lines);
+        }
+        lines
+    }
+
+    fn wrap_paragraph(&self, paragraph: &LineRange, lines: &mut Vec<LineRange>) {
+        let mut line_start = paragraph.start;
+        let mut line_end = paragraph.start;
+        for (word_start, word_end) in self.words(paragraph) {
+            if line_end > line_start && !self.fits(line_start, word_end) {
+                lines.push(LineRange {
+                    start: line_start,
+                    end: line_end,
+                    wrapped: true,
+                    ..LineRange::default()
+                });
+                line_start = word_start;
+            }
+            if line_end <= line_start {
+                line_start = word_start;
+            }
+
+            // words wider than the whole line are broken between characters; the last
+            // part stays on the line, even when wider than the box
+            let mut start = line_start;
+            while start < word_end && !self.fits(start, word_end) {
+                let end = self.longest_fitting(start, word_end);
+                if end == word_end {
+                    break;
+                }
+                lines.push(LineRange {
+                    start,
+                    end,
+                    wrapped: true,
+                    ..LineRange::default()
+                });
+                start = end;
+            }
+            line_start = start;
+            line_end = word_end;
+        }
+        lines.push(LineRange {
+            start: line_start,
+            end: line_end.max(line_start),
+            ..LineRange::default()
+        });
+    }
+
+    /// Byte ranges of the paragraph's words, separated by spaces.
+    fn words(&self, paragraph: &LineRange) -> Vec<(usize, usize)> {
+        let mut words = vec![];
+        let mut word_start = None;
+        for (index, ch) in self.text[paragraph.start..paragraph.end].char_indices() {
+            let index = paragraph.start + index;
+            match (ch == ' ', word_start) {
+                (true, Some(start)) => {
+                    words.push((start, index));
+                    word_start = None;
+                }
+                (false, None) => word_start = Some(index),
+                _ => {}
+            }
+        }
+        if let Some(start) = word_start {
+            words.push((start, paragraph.end));
+        }
+        words
+    }
+
+    fn fits(&self, start: usize, end: usize) -> bool {
+        self.width(&self.text[start..end]) <= self.max_width
+    }
+
+    fn width(&self, text: &str) -> f32 {
+        self.font.line_width(text, self.padding) as f32
+    }
+
+    /// End of the longest part of the text starting at `start` which fits, at least
+    /// one grapheme long.
+    fn longest_fitting(&self, start: usize, end: usize) -> usize {
+        let mut fitting = start;
+        for (index, grapheme, _) in graphemes(&self.text[start..end]) {
+            let grapheme_end = start + index + grapheme.len();
+            if fitting > start && !self.fits(start, grapheme_end) {
+                break;
+            }
+            fitting = grapheme_end;
+        }
+        fitting
+    }
+
+    /// Shortens the line, so it fits together with an ellipsis after it.
+    fn add_ellipsis(&self, line: &mut LineRange) {
+        line.suffix = ELLIPSIS;
+        line.wrapped = false;
+        while line.end > line.start && self.width(&line.text(self.text)) > self.max_width {
+            let last_grapheme = self.text[line.start..line.end].graphemes(true).next_back();
+            line.end -= last_grapheme.map_or(0, str::len);
+        }
+        line.end = line.start + self.text[line.start..line.end].trim_end().len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::color::WHITE;
+    use crate::renderer_ext::bitmap_font::tests::sprite_less_font;
+    use crate::renderer_ext::bitmap_font::{BitmapFontSettings, FontMetrics};
+
+    /// Font with 10 pixels wide letters, 5 pixels wide spaces and 2 pixels wide dots.
+    /// Its tallest glyphs stand 6 pixels above the base line, 2 pixels above the bottom
+    /// of its 10 pixels high lines.
+    fn font() -> BitmapFont {
+        let metrics = FontMetrics {
+            line_height: 10,
+            ascent: 6,
+            baseline: 2,
+        };
+        let mut glyphs: Vec<(char, i32)> = ('a'..='h').map(|ch| (ch, 10)).collect();
+        glyphs.extend([(' ', 5), ('.', 2)]);
+        sprite_less_font(&glyphs, metrics, BitmapFontSettings::default())
+    }
+
+    fn text_box(width: i32, height: i32) -> TextBox {
+        TextBox::new(TextBounds {
+            origin: (0, 0).into(),
+            size: (width, height).into(),
+        })
+    }
+
+    fn line_bottoms(layout: &TextLayout) -> Vec<i32> {
+        layout
+            .lines()
+            .iter()
+            .map(|line| line.bounds.origin.y)
+            .collect()
+    }
+
+    #[test]
+    fn aligns_lines_to_box_top_by_default() {
+        let layout = font().layout_in_box("ab\nab", &text_box(100, 50), &TextStyle::new(WHITE));
+        assert_eq!(line_bottoms(&layout), vec![40, 30]);
+    }
+
+    #[test]
+    fn puts_first_base_line_ascent_below_box_top() {
+        let text_box = text_box(100, 50).with_vertical_alignment(VerticalAlignment::Baseline);
+        let layout = font().layout_in_box("ab\nab", &text_box, &TextStyle::new(WHITE));
+        // the base line goes 6 pixels below the top, with the line's bottom 2 below it
+        assert_eq!(line_bottoms(&layout), vec![42, 32]);
+    }
+
+    #[test]
+    fn places_lines_inside_box_by_alignment() {
+        let bottoms = |alignment| {
+            let text_box = text_box(100, 50).with_vertical_alignment(alignment);
+            line_bottoms(&font().layout_in_box("ab\nab", &text_box, &TextStyle::new(WHITE)))
+        };
+        assert_eq!(bottoms(VerticalAlignment::Middle), vec![25, 15]);
+        assert_eq!(bottoms(VerticalAlignment::Bottom), vec![10, 0]);
+    }
+
+    fn line_texts<'a>(layout: &TextLayout, text: &'a str) -> Vec<&'a str> {
+        layout
+            .lines()
+            .iter()
+            .map(|line| &text[line.start..line.end])
+            .collect()
+    }
+
+    #[test]
+    fn wraps_lines_at_spaces_and_inside_long_words() {
+        let cases: &[(&str, i32, &[&str])] = &[
+            ("ab cd ef", 50, &["ab cd", "ef"]),
+            ("ab cd ef", 25, &["ab", "cd", "ef"]),
+            ("abcdefgh", 30, &["abc", "def", "gh"]),
+            ("ab abcdefgh", 30, &["ab", "abc", "def", "gh"]),
+            ("abcdefgh", 5, &["a", "b", "c", "d", "e", "f", "g", "h"]),
+            ("ab\n\ncd", 100, &["ab", "", "cd"]),
+            ("", 100, &[""]),
+        ];
+        for (text, width, expected) in cases {
+            let layout = font().layout_in_box(text, &text_box(*width, 100), &TextStyle::new(WHITE));
+            assert_eq!(
+                &line_texts(&layout, text),
+                expected,
+                "{:?} in {}",
+                text,
+                width
+            );
+        }
+    }
+
+    #[test]
+    fn cuts_lines_which_do_not_fit() {
+        let text = "abc d ef";
+        let layout = |overflow| {
+            let text_box = text_box(50, 15).with_overflow(overflow);
+            font().layout_in_box(text, &text_box, &TextStyle::new(WHITE))
+        };
+
+        assert_eq!(
+            line_texts(&layout(TextOverflow::Visible), text),
+            vec!["abc d", "ef"]
+        );
+        assert_eq!(line_texts(&layout(TextOverflow::Clip), text), vec!["abc d"]);
+
+        // the ellipsis does not fit after "abc d", and the space before it is dropped
+        let ellipsis = layout(TextOverflow::Ellipsis);
+        assert_eq!(line_texts(&ellipsis, text), vec!["abc"]);
+        let characters: String = ellipsis
+            .glyphs()
+            .iter()
+            .map(|glyph| glyph.character)
+            .collect();
+        assert_eq!(characters, "abc...");
+        assert_eq!(ellipsis.size(), (36, 10).into());
+    }
+
+    #[test]
+    fn keeps_a_line_in_boxes_lower_than_a_line() {
+        for overflow in [TextOverflow::Clip, TextOverflow::Ellipsis] {
+            let text_box = text_box(100, 5).with_overflow(overflow);
+            let layout = font().layout_in_box("ab ab", &text_box, &TextStyle::new(WHITE));
+            assert_eq!(layout.lines().len(), 1);
+            assert!(layout.glyphs().is_empty());
+            assert_eq!(
+                layout.bounds(),
+                TextBounds {
+                    origin: (0, 0).into(),
+                    size: (0, 0).into(),
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn stretches_spaces_of_wrapped_justified_lines() {
+        let style = TextStyle::new(WHITE).with_alignment(TextAlignment::Justified);
+        let layout = font().layout_in_box("ab cd ef", &text_box(50, 100), &style);
+        let spans: Vec<(i32, i32)> = layout
+            .glyphs()
+            .iter()
+            .map(|glyph| (glyph.bounds.origin.x, glyph.bounds.size.x))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                (0, 10),
+                (10, 10),
+                (20, 10),
+                (30, 10),
+                (40, 10),
+                (0, 10),
+                (10, 10)
+            ]
+        );
+        let widths: Vec<i32> = layout
+            .lines()
+            .iter()
+            .map(|line| line.bounds.size.x)
+            .collect();
+        // the last line of the paragraph is not stretched
+        assert_eq!(widths, vec![50, 20]);
+    }
+}
@@ -0,0 +1,261 @@
+use crate::Sprite;
+use cgmath::Vector2;
+use std::borrow::Cow;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Axis-aligned rectangle in world coordinates, with `origin` at its bottom-left corner.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TextBounds {
+    pub origin: Vector2<i32>,
+    pub size: Vector2<i32>,
+}
+
+impl TextBounds {
+    pub fn contains(&self, point: Vector2<i32>) -> bool {
+        point.x >= self.origin.x
+            && point.y >= self.origin.y
+            && point.x < self.origin.x + self.size.x
+            && point.y < self.origin.y + self.size.y
+    }
+
+    /// Smallest rectangle containing both rectangles.
+    pub fn union(&self, other: &TextBounds) -> TextBounds {
+        let min = Vector2::new(
+            self.origin.x.min(other.origin.x),
+            self.origin.y.min(other.origin.y),
+        );
+        let max = Vector2::new(
+            (self.origin.x + self.size.x).max(other.origin.x + other.size.x),
+            (self.origin.y + self.size.y).max(other.origin.y + other.size.y),
+        );
+        TextBounds {
+            origin: min,
+            size: max - min,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PositionedGlyph {
+    /// Position of the character in the laid out text, in bytes.
+    pub byte_index: usize,
+    /// Length of the grapheme cluster drawn by the glyph, in bytes; zero for glyphs
+    /// of an ellipsis added to the text.
+    pub byte_len: usize,
+    /// First character of the grapheme cluster, which it is drawn as.
+    pub character: char,
+    pub line: usize,
+    /// Pen position the glyph's sprite is drawn at.
+    pub position: Vector2<i32>,
+    /// Box taken by the glyph: its advance wide and its line high.
+    pub bounds: TextBounds,
+    /// `None` for characters drawn as empty space.
+    pub sprite: Option<Sprite>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TextLine {
+    pub bounds: TextBounds,
+    /// Byte range of the line in the text, without the line break.
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Grapheme clusters of the text with their byte indices and first characters, which
+/// fonts draw them as.
+pub(in crate::renderer_ext) fn graphemes(text: &str) -> impl Iterator<Item = (usize, &str, char)> {
+    text.grapheme_indices(true).filter_map(|(index, grapheme)| {
+        let character = grapheme.chars().next()?;
+        Some((index, grapheme, character))
+    })
+}
+
+/// Part of a text laid out as a single line.
+#[derive(Debug, Clone, Default)]
+pub(in crate::renderer_ext) struct LineRange {
+    pub(in crate::renderer_ext) start: usize,
+    pub(in crate::renderer_ext) end: usize,
+    /// Set for lines broken by wrapping, which justified text stretches; the last line
+    /// of a paragraph is not.
+    pub(in crate::renderer_ext) wrapped: bool,
+    /// Drawn after the line's text, like an ellipsis of truncated text.
+    pub(in crate::renderer_ext) suffix: &'static str,
+}
+
+impl LineRange {
+    /// Lines of the text as broken by line breaks; empty text still has a single line.
+    pub(in crate::renderer_ext) fn split_lines(text: &str) -> Vec<LineRange> {
+        let mut lines = vec![];
+        let mut line_start = 0;
+        for line in text
+            .split_inclusive('\n')
+            .chain(text.is_empty().then_some(""))
+        {
+            lines.push(LineRange {
+                start: line_start,
+                end: line_start + line.trim_end_matches(['\n', '\r']).len(),
+                ..LineRange::default()
+            });
+            line_start += line.len();
+        }
+        lines
+    }
+
+    pub(in crate::renderer_ext) fn text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self.suffix {
+            "" => Cow::Borrowed(&text[self.start..self.end]),
+            suffix => Cow::Owned(format!("{}{}", &text[self.start..self.end], suffix)),
+        }
+    }
+}
+
+/// Text laid out by a font: every glyph with the place it is drawn at.
+#[derive(Debug, Clone)]
+pub struct TextLayout {
+    glyphs: Vec<PositionedGlyph>,
+    lines: Vec<TextLine>,
+}
+
+impl TextLayout {
+    pub(in crate::renderer_ext) fn new(
+        glyphs: Vec<PositionedGlyph>,
+        lines: Vec<TextLine>,
+    ) -> TextLayout {
+        TextLayout { glyphs, lines }
+    }
+
+    pub fn glyphs(&self) -> &[PositionedGlyph] {
+        &self.glyphs
+    }
+
+    /// All lines, including empty ones; there is always at least one.
+    pub fn lines(&self) -> &[TextLine] {
+        &self.lines
+    }
+
+    /// Bounding box of all lines; empty text has a zero-sized box at the drawing position.
+    pub fn bounds(&self) -> TextBounds {
+        self.lines
+            .iter()
+            .skip(1)
+            .fold(self.lines[0].bounds, |bounds, line| {
+                bounds.union(&line.bounds)
+            })
+    }
+
+    pub fn size(&self) -> Vector2<i32> {
+        self.bounds().size
+    }
+
+    /// Drops glyphs sticking out of the bounds horizontally.
+    pub(in crate::renderer_ext) fn clip_horizontally(&mut self, bounds: &TextBounds) {
+        self.glyphs.retain(|glyph| {
+            glyph.bounds.origin.x >= bounds.origin.x
+                && glyph.bounds.origin.x + glyph.bounds.size.x <= bounds.origin.x + bounds.size.x
+        });
+    }
+
+    /// Glyph whose box contains the point.
+    pub fn glyph_at(&self, point: Vector2<i32>) -> Option<&PositionedGlyph> {
+        self.glyphs
+            .iter()
+            .find(|glyph| glyph.bounds.contains(point))
+    }
+
+    /// Byte index in the text where a caret placed at the point would go: before the glyph
+    /// under the point, or after it when the point is in its right half. Points beside
+    /// a line go to its start or end.
+    pub fn caret_at(&self, point: Vector2<i32>) -> Option<usize> {
+        let (line_id, line) = self.lines.iter().enumerate().find(|(_, line)| {
+            point.y >= line.bounds.origin.y && point.y < line.bounds.origin.y + line.bounds.size.y
+        })?;
+        let mut caret = line.start;
+        for glyph in self.glyphs.iter().filter(|glyph| glyph.line == line_id) {
+            let middle = glyph.bounds.origin.x + glyph.bounds.size.x / 2;
+            if point.x < middle {
+                return Some(glyph.byte_index);
+            }
+            caret = glyph.byte_index + glyph.byte_len;
+        }
+        Some(caret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::color::WHITE;
+    use crate::renderer_ext::bitmap_font::tests::sprite_less_font;
+    use crate::renderer_ext::bitmap_font::{
+        BitmapFont, BitmapFontSettings, FontMetrics, TextStyle,
+    };
+
+    /// Font with 10 pixels high lines, where `b` moves 1 pixel closer to `a`.
+    fn font() -> BitmapFont {
+        let metrics = FontMetrics {
+            line_height: 10,
+            ascent: 8,
+            baseline: 2,
+        };
+        let glyphs = [('a', 6), ('b', 4), ('.', 2)];
+        let mut font = sprite_less_font(&glyphs, metrics, BitmapFontSettings::default());
+        font.insert_kerning('a', 'b', -1);
+        font
+    }
+
+    fn glyph_spans(layout: &TextLayout) -> Vec<(i32, i32)> {
+        layout
+            .glyphs()
+            .iter()
+            .map(|glyph| (glyph.bounds.origin.x, glyph.bounds.size.x))
+            .collect()
+    }
+
+    #[test]
+    fn measures_lines_with_kerning_and_padding() {
+        let font = font();
+        assert_eq!(font.line_width("ab", 0), 9);
+        // padding goes between glyphs, not around the line
+        assert_eq!(font.line_width("aab", 1), 19);
+        let style = TextStyle::new(WHITE).with_padding((1, 1).into());
+        assert_eq!(font.measure("ab\naab", &style), (19, 22).into());
+        assert_eq!(font.measure("", &style), (0, 10).into());
+    }
+
+    #[test]
+    fn finds_glyphs_and_carets_at_points() {
+        let layout = font().layout("ab", (0, 0).into(), &TextStyle::new(WHITE));
+        assert_eq!(glyph_spans(&layout), vec![(0, 6), (5, 4)]);
+        assert_eq!(layout.glyph_at((1, 5).into()).unwrap().character, 'a');
+        assert!(layout.glyph_at((20, 5).into()).is_none());
+
+        let caret = |x: i32| layout.caret_at((x, 5).into());
+        assert_eq!(caret(-5), Some(0));
+        assert_eq!(caret(2), Some(0));
+        // from the middle of a glyph on, the caret goes after it
+        assert_eq!(caret(3), Some(1));
+        assert_eq!(caret(6), Some(1));
+        assert_eq!(caret(7), Some(2));
+        assert_eq!(caret(100), Some(2));
+        assert_eq!(layout.caret_at((0, 15).into()), None);
+    }
+
+    #[test]
+    fn keeps_carets_of_suffixes_at_line_end() {
+        let line = LineRange {
+            start: 0,
+            end: 2,
+            suffix: "...",
+            ..LineRange::default()
+        };
+        let layout =
+            font().layout_lines("abab", &[line], (0, 0).into(), &TextStyle::new(WHITE), None);
+        let suffix: Vec<(usize, usize)> = layout.glyphs()[2..]
+            .iter()
+            .map(|glyph| (glyph.byte_index, glyph.byte_len))
+            .collect();
+        assert_eq!(suffix, vec![(2, 0), (2, 0), (2, 0)]);
+        assert_eq!(layout.caret_at((10, 5).into()), Some(2));
+        assert_eq!(layout.caret_at((14, 5).into()), Some(2));
+    }
+}
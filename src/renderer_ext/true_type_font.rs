@@ -0,0 +1,277 @@
+use crate::renderer::Error;
+use crate::renderer_ext::bitmap_font::{BitmapFont, BitmapFontSettings, FontMetrics, Glyph};
+use crate::{Renderer, Sprite};
+use cgmath::Vector2;
+use image::{imageops, Rgba, RgbaImage};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::ops::Deref;
+
+const INITIAL_ATLAS_SIZE: u32 = 256;
+/// Empty pixels left around each glyph, so neighbours do not bleed in when sampling.
+const GLYPH_PADDING: u32 = 1;
+
+/// TrueType or OpenType font rasterized at a fixed pixel size.
+///
+/// Glyphs are rasterized into an atlas texture, which grows when full. Printable ASCII is
+/// cached on load. Other characters missing while laying out text are rasterized by
+/// `upload_pending`, or ahead of time by `cache_text`.
+/// The font is drawn like any `BitmapFont`, with the drawing position at the bottom of the line.
+pub struct TrueTypeFont {
+    font: fontdue::Font,
+    pixel_size: f32,
+    descent: i32,
+    atlas: GlyphAtlas,
+    bitmap_font: BitmapFont,
+    /// Characters the font has no glyphs for, already reported.
+    unavailable: HashSet<char>,
+}
+
+impl TrueTypeFont {
+    pub fn load(
+        renderer: &mut Renderer,
+        path: &str,
+        pixel_size: f32,
+        settings: BitmapFontSettings,
+    ) -> Result<TrueTypeFont, Error> {
+        Self::from_bytes(renderer, &fs::read(path)?, pixel_size, settings)
+    }
+
+    pub fn from_bytes(
+        renderer: &mut Renderer,
+        data: &[u8],
+        pixel_size: f32,
+        settings: BitmapFontSettings,
+    ) -> Result<TrueTypeFont, Error> {
+        let font = fontdue::Font::from_bytes(data, fontdue::FontSettings::default())
+            .map_err(|reason| Error::InvalidFont(reason.to_string()))?;
+        let line_metrics = font
+            .horizontal_line_metrics(pixel_size)
+            .ok_or_else(|| Error::InvalidFont("font has no horizontal metrics".to_string()))?;
+
+        let mut true_type_font = TrueTypeFont {
+            font,
+            pixel_size,
+            descent: line_metrics.descent.floor() as i32,
+            atlas: GlyphAtlas::new(renderer),
+            bitmap_font: BitmapFont::empty(
+                FontMetrics {
+                    line_height: line_metrics.new_line_size.ceil() as i32,
+                    ascent: line_metrics.ascent.ceil() as i32,
+                    baseline: -line_metrics.descent.floor() as i32,
+                },
+                settings,
+            ),
+            unavailable: HashSet::new(),
+        };
+        let ascii: String = (' '..='~').collect();
+        true_type_font.cache_text(renderer, &ascii)?;
+        Ok(true_type_font)
+    }
+
+    pub fn get_pixel_size(&self) -> f32 {
+        self.pixel_size
+    }
+
+    /// Rasterizes characters laid out since the last call which were not in the atlas.
+    /// Called before rendering every frame, it makes them drawn from the next frame on.
+    pub fn upload_pending(&mut self, renderer: &mut Renderer) -> Result<(), Error> {
+        let pending: String = self
+            .bitmap_font
+            .take_requested_characters()
+            .into_iter()
+            .collect();
+        self.cache_text(renderer, &pending)
+    }
+
+    /// Rasterizes all characters of the text which are not in the atlas yet.
+    pub fn cache_text(&mut self, renderer: &mut Renderer, text: &str) -> Result<(), Error> {
+        let mut added = vec![];
+        for character in text.chars() {
+            if character.is_control()
+                || added.contains(&character)
+                || self.bitmap_font.get_glyph(character).is_some()
+            {
+                continue;
+            }
+            if self.font.lookup_glyph_index(character) == 0 {
+                if self.unavailable.insert(character) {
+                    log::warn!(
+                        "Missing character {:?} ({:#x})",
+                        character,
+                        character as u32
+                    );
+                }
+                continue;
+            }
+            self.cache_glyph(renderer, character)?;
+            added.push(character);
+        }
+
+        for first in added.iter().copied() {
+            for second in self.bitmap_font.characters().collect::<Vec<_>>() {
+                self.cache_kerning(first, second);
+                self.cache_kerning(second, first);
+            }
+        }
+        Ok(())
+    }
+
+    fn cache_glyph(&mut self, renderer: &mut Renderer, character: char) -> Result<(), Error> {
+        let (metrics, coverage) = self.font.rasterize(character, self.pixel_size);
+        let size = Vector2::new(metrics.width as u32, metrics.height as u32);
+        let advance = metrics.advance_width.round() as i32;
+        if size.x == 0 || size.y == 0 {
+            let glyph = Glyph {
+                sprite: None,
+                advance,
+            };
+            self.bitmap_font.insert_glyph(character, glyph);
+            return Ok(());
+        }
+
+        // white glyphs with coverage as alpha, so they can be tinted while drawing
+        let pixels = RgbaImage::from_fn(size.x, size.y, |x, y| {
+            Rgba([255, 255, 255, coverage[(y * size.x + x) as usize]])
+        });
+        let atlas_size = self.atlas.pixels.dimensions();
+        let placement = GlyphPlacement {
+            offset: self.atlas.allocate(renderer, size)?,
+            size,
+            pivot: (-metrics.xmin, self.descent - metrics.ymin).into(),
+            advance,
+        };
+        self.atlas.write(renderer, placement.offset, &pixels)?;
+        self.atlas.placements.insert(character, placement);
+
+        if self.atlas.pixels.dimensions() != atlas_size {
+            self.refresh_glyphs()
+        } else {
+            self.insert_placed_glyph(character, placement)
+        }
+    }
+
+    fn cache_kerning(&mut self, first: char, second: char) {
+        let amount = self
+            .font
+            .horizontal_kern(first, second, self.pixel_size)
+            .map_or(0, |amount| amount.round() as i32);
+        if amount != 0 {
+            self.bitmap_font.insert_kerning(first, second, amount);
+        }
+    }
+
+    /// Slices sprites of all placed glyphs again, as the atlas texture may have been replaced.
+    fn refresh_glyphs(&mut self) -> Result<(), Error> {
+        let placements: Vec<_> = self.atlas.placements.clone().into_iter().collect();
+        for (character, placement) in placements {
+            self.insert_placed_glyph(character, placement)?;
+        }
+        Ok(())
+    }
+
+    fn insert_placed_glyph(
+        &mut self,
+        character: char,
+        placement: GlyphPlacement,
+    ) -> Result<(), Error> {
+        let sprite = self
+            .atlas
+            .sprite
+            .slice(placement.size, placement.offset)?
+            .with_pivot(placement.pivot);
+        let glyph = Glyph {
+            sprite: Some(sprite),
+            advance: placement.advance,
+        };
+        self.bitmap_font.insert_glyph(character, glyph);
+        Ok(())
+    }
+}
+
+impl Deref for TrueTypeFont {
+    type Target = BitmapFont;
+
+    fn deref(&self) -> &Self::Target {
+        &self.bitmap_font
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct GlyphPlacement {
+    offset: Vector2<u32>,
+    size: Vector2<u32>,
+    pivot: Vector2<i32>,
+    advance: i32,
+}
+
+/// Texture glyphs are packed into, in rows of glyphs called shelves.
+struct GlyphAtlas {
+    sprite: Sprite,
+    /// Copy of the texture's pixels, used to fill the bigger texture when the atlas grows.
+    pixels: RgbaImage,
+    placements: HashMap<char, GlyphPlacement>,
+    shelf_position: Vector2<u32>,
+    shelf_height: u32,
+}
+
+impl GlyphAtlas {
+    fn new(renderer: &mut Renderer) -> GlyphAtlas {
+        let size = Vector2::new(INITIAL_ATLAS_SIZE, INITIAL_ATLAS_SIZE);
+        GlyphAtlas {
+            sprite: renderer.create_dynamic_sprite(size),
+            pixels: RgbaImage::new(size.x, size.y),
+            placements: HashMap::new(),
+            shelf_position: (0, 0).into(),
+            shelf_height: 0,
+        }
+    }
+
+    /// Finds a free place for a glyph, growing the atlas if needed.
+    fn allocate(
+        &mut self,
+        renderer: &mut Renderer,
+        size: Vector2<u32>,
+    ) -> Result<Vector2<u32>, Error> {
+        let padded = size + Vector2::new(GLYPH_PADDING, GLYPH_PADDING);
+        if self.shelf_position.x + padded.x > self.pixels.width() {
+            self.shelf_position = (0, self.shelf_position.y + self.shelf_height).into();
+            self.shelf_height = 0;
+        }
+        while self.shelf_position.x + padded.x > self.pixels.width()
+            || self.shelf_position.y + padded.y > self.pixels.height()
+        {
+            self.grow(renderer)?;
+        }
+
+        let offset = self.shelf_position + Vector2::new(GLYPH_PADDING, GLYPH_PADDING);
+        self.shelf_position.x += padded.x;
+        self.shelf_height = self.shelf_height.max(padded.y);
+        Ok(offset)
+    }
+
+    fn grow(&mut self, renderer: &mut Renderer) -> Result<(), Error> {
+        let (width, height) = self.pixels.dimensions();
+        let new_size = if width > height {
+            Vector2::new(width, height * 2)
+        } else {
+            Vector2::new(width * 2, height)
+        };
+        let mut pixels = RgbaImage::new(new_size.x, new_size.y);
+        imageops::replace(&mut pixels, &self.pixels, 0, 0);
+
+        self.sprite = renderer.create_dynamic_sprite(new_size);
+        self.pixels = pixels;
+        renderer.update_sprite_pixels(&self.sprite, (0, 0).into(), &self.pixels)
+    }
+
+    fn write(
+        &mut self,
+        renderer: &Renderer,
+        offset: Vector2<u32>,
+        pixels: &RgbaImage,
+    ) -> Result<(), Error> {
+        imageops::replace(&mut self.pixels, pixels, offset.x as i64, offset.y as i64);
+        renderer.update_sprite_pixels(&self.sprite, offset, pixels)
+    }
+}